@@ -0,0 +1,42 @@
+//! Encrypting the log at rest.
+//!
+//! hmdb doesn't implement any encryption itself. Implement `Cipher` with an AEAD, AES-GCM or
+//! ChaCha20-Poly1305 for example, and open the db with it:
+//!
+//! ```ignore,rust
+//! let db = SchemaName::options()
+//!     .cipher(Arc::new(MyCipher::new(key)))
+//!     .open("db_dir")
+//!     .unwrap();
+//! ```
+//!
+//! Every record is sealed on its own, after it's encoded and before it's framed, so the checksums
+//! of the framing cover the sealed bytes. The header of the log, which names the tables and their
+//! types, stays readable, and records whether the log is sealed. A log that isn't is refused when
+//! it's opened with a cipher, with `Error::Decryption`, since anyone could have written it. To
+//! seal a log written without a cipher, open it once with `OpenOptions::seal_plaintext`.
+//!
+//! In a sealed log, a record that fails its checksum, or doesn't open, fails with
+//! `Error::Decryption`, even with `RecoveryPolicy::salvage`. Only a record cut short by the end of
+//! the log is dropped, as what a crash leaves behind.
+//!
+//! To rotate the key, call `LogCompacter::rotate_cipher` with a cipher holding the new key. It
+//! rewrites the log sealed with the new key, and seals every record after it with the new key.
+//! If the process crashes during the rotation, the log is sealed with either the old or the new
+//! key, a cipher that can open both is the easiest way to handle that.
+
+use std::fmt::Debug;
+
+/// What a cipher fails with. It ends up as the message of `Error::Decryption`, or of
+/// `Error::SerializeError` for `seal`.
+pub type CipherError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Authenticated encryption of the records of a log. `Debug` shouldn't print the key.
+pub trait Cipher: Send + Sync + Debug {
+    /// Encrypts and authenticates a record. Use a fresh nonce for every record, and include it
+    /// in what's returned.
+    fn seal(&self, record: &[u8]) -> Result<Vec<u8>, CipherError>;
+
+    /// Reverses `seal`. Fails if `sealed` was sealed with another key, or was tampered with.
+    fn open(&self, sealed: &[u8]) -> Result<Vec<u8>, CipherError>;
+}
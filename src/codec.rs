@@ -0,0 +1,52 @@
+//! How the keys and values of a schema are encoded in its log.
+//!
+//! The codec is chosen per schema, bincode unless `schema!` names another one:
+//!
+//! ```ignore,rust
+//! hmdb::schema! {
+//!     #[codec = Json]
+//!     SchemaName {
+//!         accounts: <Username, Account>
+//!     }
+//! }
+//! ```
+//!
+//! The codec only encodes the events of the tables. The header of the log, the framing of its
+//! records, and which table a record belongs to are encoded by hmdb. A self-describing codec lets
+//! a value gain optional fields, `#[serde(default)]` ones, without upcasting or a migration.
+//!
+//! The log records the `Codec::NAME` of the codec that wrote it, and can only be opened with
+//! that codec. To change the codec of a schema, declare a new version of it, see `migration`.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// What a codec fails with. It ends up as the message of `Error::SerializeError` or
+/// `Error::LogParseError`.
+pub type CodecError = Box<dyn std::error::Error + Send + Sync>;
+
+pub trait Codec {
+    /// Recorded in the header of the log.
+    const NAME: &'static str;
+
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, CodecError>;
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, CodecError>;
+}
+
+/// The default codec, compact and fast. It isn't self-describing, so the types of a table can
+/// only change by upcasting or migrating.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Bincode;
+
+impl Codec for Bincode {
+    const NAME: &'static str = "bincode";
+
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, CodecError> {
+        Ok(bincode::serialize(value)?)
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, CodecError> {
+        Ok(bincode::deserialize(bytes)?)
+    }
+}
@@ -0,0 +1,272 @@
+//! Deciding when the background compacter compacts the log.
+//!
+//! Without a `CompactionPolicy`, `LogCompacter::start_background_compacter` compacts on every
+//! tick. With one, it only compacts once the log has grown enough to be worth it:
+//!
+//! ```ignore,rust
+//! let db = SchemaName::options()
+//!     .compaction(CompactionPolicy {
+//!         ratio: Some(2.0),
+//!         records: Some(100_000),
+//!         min_bytes: 1 << 20,
+//!         ..Default::default()
+//!     })
+//!     .open("db_dir")
+//!     .unwrap();
+//! let compacter = db.start_background_compacter(Duration::from_secs(10)).unwrap();
+//! ```
+//!
+//! The writer keeps `LogStats` up to date as it writes, so checking the policy on every tick
+//! costs nothing.
+//!
+//! The compacter runs until the `CompacterHandle` it returns is stopped or dropped, and can be
+//! paused for bulk loads, see `CompacterHandle`.
+
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime};
+
+use tracing::error;
+
+use crate::errors::Error;
+use crate::log::LogCompacter;
+
+/// The size of the log, and how much was written to it since it was last compacted, see
+/// `LogCompacter::log_stats`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LogStats {
+    /// Bytes in the segments of the log that the snapshot doesn't cover, see `log::LogFiles`.
+    pub segment_bytes: u64,
+    /// Bytes in the snapshot, the live data as of the last compaction.
+    pub snapshot_bytes: u64,
+    /// Records this process appended since the last compaction, or since the db was opened.
+    pub records: u64,
+}
+
+impl LogStats {
+    /// Bytes in every file of the log.
+    pub fn log_bytes(&self) -> u64 {
+        self.segment_bytes + self.snapshot_bytes
+    }
+}
+
+/// When the background compacter compacts the log: once any of the triggers that are set is
+/// reached. A policy without triggers never compacts.
+#[derive(Clone, Debug, Default)]
+pub struct CompactionPolicy {
+    /// Compact once the files of the log add up to this many bytes.
+    pub log_bytes: Option<u64>,
+
+    /// Compact once the log is this many times the size of the live data, as of the last
+    /// compaction. A log that was never compacted reaches any ratio. Must be more than 1.
+    pub ratio: Option<f64>,
+
+    /// Compact once this many records were appended since the last compaction.
+    pub records: Option<u64>,
+
+    /// Never compact a log smaller than this many bytes, whatever the triggers say. Keeps a small
+    /// log, whose ratio swings with every write, from being compacted over and over.
+    pub min_bytes: u64,
+}
+
+impl CompactionPolicy {
+    /// Whether a log with `stats` is due for a compaction.
+    pub fn triggered(&self, stats: &LogStats) -> bool {
+        let log_bytes = stats.log_bytes();
+        if log_bytes < self.min_bytes {
+            return false;
+        }
+
+        let by_size = self.log_bytes.is_some_and(|max| log_bytes >= max);
+        let by_ratio = self
+            .ratio
+            .is_some_and(|ratio| log_bytes as f64 >= ratio * stats.snapshot_bytes as f64);
+        let by_records = self.records.is_some_and(|max| stats.records >= max);
+        by_size || by_ratio || by_records
+    }
+}
+
+/// What the background compacter has done so far, see `CompacterHandle::stats`.
+#[derive(Clone, Debug, Default)]
+pub struct CompacterStats {
+    /// Compactions run, whether they succeeded or not.
+    pub compactions: u64,
+    /// When the last compaction started.
+    pub last_run: Option<SystemTime>,
+    /// How long the last compaction took.
+    pub last_duration: Duration,
+    /// `LogStats::log_bytes` before the last compaction.
+    pub bytes_before: u64,
+    /// `LogStats::log_bytes` after the last compaction.
+    pub bytes_after: u64,
+    /// The error the last compaction failed with, `None` if it succeeded.
+    pub last_error: Option<String>,
+}
+
+/// Controls the thread started by `LogCompacter::start_background_compacter`. Dropping the
+/// handle stops the thread, like `stop`.
+pub struct CompacterHandle {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+struct Shared {
+    compacter: Box<dyn LogCompacter + Send + Sync>,
+    due: Box<dyn Fn() -> Result<bool, Error> + Send + Sync>,
+    state: Mutex<State>,
+    wake: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    stopped: bool,
+    paused: bool,
+    stats: CompacterStats,
+}
+
+impl CompacterHandle {
+    /// Starts a thread that compacts with `compacter` every `interval`, when `due` says so.
+    #[doc(hidden)]
+    pub fn spawn(
+        compacter: Box<dyn LogCompacter + Send + Sync>,
+        due: Box<dyn Fn() -> Result<bool, Error> + Send + Sync>,
+        interval: Duration,
+    ) -> Result<Self, Error> {
+        let shared = Arc::new(Shared {
+            compacter,
+            due,
+            state: Mutex::new(State::default()),
+            wake: Condvar::new(),
+        });
+
+        let background = shared.clone();
+        let thread = thread::Builder::new()
+            .name("hmdb-compacter".to_string())
+            .spawn(move || background.run_every(interval))
+            .map_err(|err| {
+                Error::OsError(
+                    format!("Failed to start the background compacter, error: {}", err),
+                    err,
+                )
+            })?;
+
+        Ok(Self {
+            shared,
+            thread: Some(thread),
+        })
+    }
+
+    /// Stops the thread, waiting for a compaction that is running to finish.
+    pub fn stop(self) {
+        drop(self)
+    }
+
+    /// Skips the ticks of the thread until `resume`, for bulk loads. A compaction that is running
+    /// still finishes, and `compact_now` still compacts.
+    pub fn pause(&self) -> Result<(), Error> {
+        self.shared.lock()?.paused = true;
+        Ok(())
+    }
+
+    pub fn resume(&self) -> Result<(), Error> {
+        self.shared.lock()?.paused = false;
+        Ok(())
+    }
+
+    /// Compacts the log on the calling thread, whatever the `CompactionPolicy` says, and counts
+    /// the compaction in `stats`.
+    pub fn compact_now(&self) -> Result<(), Error> {
+        self.shared.compact()
+    }
+
+    pub fn stats(&self) -> Result<CompacterStats, Error> {
+        Ok(self.shared.lock()?.stats.clone())
+    }
+}
+
+impl Drop for CompacterHandle {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.shared.state.lock() {
+            state.stopped = true;
+        }
+        self.shared.wake.notify_all();
+        if let Some(thread) = self.thread.take() {
+            thread.join().unwrap_or(());
+        }
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> Result<MutexGuard<'_, State>, Error> {
+        self.state.lock().map_err(Error::lock_error)
+    }
+
+    /// Compacts when `due` every `interval`, until stopped. A failed compaction is logged and
+    /// kept in the stats, and the next tick tries again.
+    fn run_every(&self, interval: Duration) {
+        let mut next_tick = Instant::now() + interval;
+        loop {
+            let mut state = match self.lock() {
+                Ok(state) => state,
+                Err(err) => {
+                    error!("background compacter stopped: {:?}", err);
+                    return;
+                }
+            };
+            loop {
+                let now = Instant::now();
+                if state.stopped {
+                    return;
+                }
+                if now >= next_tick {
+                    break;
+                }
+                state = match self.wake.wait_timeout(state, next_tick - now) {
+                    Ok((state, _)) => state,
+                    Err(err) => {
+                        error!("background compacter stopped: {:?}", Error::lock_error(err));
+                        return;
+                    }
+                };
+            }
+            let paused = state.paused;
+            drop(state);
+
+            next_tick += interval;
+            if paused {
+                continue;
+            }
+            let compacted = match (self.due)() {
+                Ok(true) => self.compact(),
+                Ok(false) => Ok(()),
+                Err(err) => Err(err),
+            };
+            if let Err(err) = compacted {
+                error!("failed to compact log in background compacter: {:?}", err);
+            }
+        }
+    }
+
+    fn compact(&self) -> Result<(), Error> {
+        let last_run = SystemTime::now();
+        let start = Instant::now();
+        let before = self.compacter.log_stats()?;
+        let compacted = self.compacter.compact_log();
+        let after = self.compacter.log_stats();
+
+        let mut state = self.lock()?;
+        state.stats = CompacterStats {
+            compactions: state.stats.compactions + 1,
+            last_run: Some(last_run),
+            last_duration: start.elapsed(),
+            bytes_before: before.log_bytes(),
+            bytes_after: after.as_ref().map_or(0, LogStats::log_bytes),
+            last_error: compacted.as_ref().err().map(|err| format!("{:?}", err)),
+        };
+        drop(state);
+
+        compacted?;
+        after.map(|_| ())
+    }
+}
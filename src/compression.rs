@@ -0,0 +1,200 @@
+//! Compressing the records of the log.
+//!
+//! Compression is off unless the db is opened with a `Compression`, which uses the built-in `Lz`
+//! compressor by default:
+//!
+//! ```ignore,rust
+//! let db = SchemaName::options()
+//!     .compression(Compression::default())
+//!     .open("db_dir")
+//!     .unwrap();
+//! ```
+//!
+//! Records are compressed after they're encoded, and before they're sealed by a `Cipher`. Only
+//! records of at least `Compression::min_size` bytes are compressed, and only kept compressed if
+//! that made them smaller, so the framing of every record says whether it's compressed. A
+//! snapshot is written as records of about 64 MiB of entries each, which are compressed one at a
+//! time. Records of more than 4 GiB are never compressed.
+//!
+//! The header of the log names the compressor, a log compressed by `Lz` can always be read, one
+//! compressed by another `Compressor` only by opening the db with it. A log is recompressed, or
+//! decompressed, when it's opened for writing with another compressor, or without one.
+
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use crate::errors::Error;
+
+/// What decompressing fails with. It ends up as the message of `Error::Decompression`.
+pub type CompressionError = Box<dyn std::error::Error + Send + Sync>;
+
+pub trait Compressor: Send + Sync + Debug {
+    /// Recorded in the header of the log.
+    fn name(&self) -> &'static str;
+
+    fn compress(&self, record: &[u8]) -> Vec<u8>;
+
+    fn decompress(&self, compressed: &[u8]) -> Result<Vec<u8>, CompressionError>;
+}
+
+/// Which records are compressed, and how.
+#[derive(Clone, Debug)]
+pub struct Compression {
+    pub compressor: Arc<dyn Compressor>,
+
+    /// Records smaller than this many bytes are written as they are.
+    pub min_size: usize,
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Self {
+            compressor: Arc::new(Lz),
+            min_size: 256,
+        }
+    }
+}
+
+impl Compression {
+    /// The compressed record, if it's worth compressing. Records of more than 4 GiB are written
+    /// as they are, `Lz` records the length of what it compresses as a u32.
+    pub(crate) fn compress(&self, record: &[u8]) -> Option<Vec<u8>> {
+        if record.len() < self.min_size || u32::try_from(record.len()).is_err() {
+            return None;
+        }
+        Some(self.compressor.compress(record)).filter(|compressed| compressed.len() < record.len())
+    }
+}
+
+/// The compressor that decompresses the records of a log whose header names `name`, `configured`
+/// is the one the db was opened with. `Lz` if the log isn't compressed, its records can't be.
+pub(crate) fn resolve<'a>(
+    name: Option<&str>,
+    configured: Option<&'a dyn Compressor>,
+) -> Result<&'a dyn Compressor, Error> {
+    match (name, configured) {
+        (Some(name), Some(configured)) if configured.name() == name => Ok(configured),
+        (Some(name), _) if name != Lz.name() => Err(Error::Decompression(format!(
+            "The log is compressed by `{}`, open the db with that `Compressor`.",
+            name
+        ))),
+        _ => Ok(&Lz),
+    }
+}
+
+/// A byte oriented LZ77 compressor, fast, and good at the repetition within serialized structs
+/// and blobs, though it compresses less than a general purpose compressor would.
+///
+/// The compressed record is the size of the record, a u32 BE, followed by runs. A run starts with
+/// a control byte, if its high bit is clear it's followed by `control + 1` literal bytes, if it's
+/// set the run repeats `(control & 0x7f) + MIN_MATCH` bytes starting an offset, a u16 BE, back
+/// from the end of what was decompressed so far.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Lz;
+
+const MIN_MATCH: usize = 4;
+const MAX_MATCH: usize = 0x7f + MIN_MATCH;
+const MAX_LITERALS: usize = 0x80;
+const MAX_OFFSET: usize = u16::MAX as usize;
+const HASH_BITS: u32 = 14;
+
+impl Lz {
+    fn hash(bytes: &[u8]) -> usize {
+        let word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        (word.wrapping_mul(0x9e37_79b1) >> (32 - HASH_BITS)) as usize
+    }
+
+    fn flush_literals(out: &mut Vec<u8>, literals: &[u8]) {
+        for chunk in literals.chunks(MAX_LITERALS) {
+            out.push((chunk.len() - 1) as u8);
+            out.extend_from_slice(chunk);
+        }
+    }
+}
+
+impl Compressor for Lz {
+    fn name(&self) -> &'static str {
+        "lz"
+    }
+
+    fn compress(&self, record: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(record.len() / 2 + 8);
+        out.extend_from_slice(&(record.len() as u32).to_be_bytes());
+
+        // The last position each hash of four bytes was seen at, plus one so zero means never
+        let mut seen = vec![0usize; 1 << HASH_BITS];
+        let mut literals = 0;
+        let mut index = 0;
+        while index + MIN_MATCH <= record.len() {
+            let hash = Self::hash(&record[index..]);
+            let candidate = seen[hash].checked_sub(1);
+            seen[hash] = index + 1;
+
+            let matched = candidate
+                .filter(|candidate| index - candidate <= MAX_OFFSET)
+                .map(|candidate| {
+                    let limit = (record.len() - index).min(MAX_MATCH);
+                    let len = (0..limit)
+                        .take_while(|i| record[candidate + i] == record[index + i])
+                        .count();
+                    (index - candidate, len)
+                })
+                .filter(|(_, len)| *len >= MIN_MATCH);
+
+            match matched {
+                Some((offset, len)) => {
+                    Self::flush_literals(&mut out, &record[literals..index]);
+                    out.push(0x80 | (len - MIN_MATCH) as u8);
+                    out.extend_from_slice(&(offset as u16).to_be_bytes());
+                    index += len;
+                    literals = index;
+                }
+                None => index += 1,
+            }
+        }
+        Self::flush_literals(&mut out, &record[literals..]);
+
+        out
+    }
+
+    fn decompress(&self, compressed: &[u8]) -> Result<Vec<u8>, CompressionError> {
+        let (size, mut runs) = match compressed {
+            [a, b, c, d, runs @ ..] => (u32::from_be_bytes([*a, *b, *c, *d]) as usize, runs),
+            _ => return Err("the record is too short to hold its size".into()),
+        };
+
+        // A match of up to MAX_MATCH bytes takes three, don't trust a size beyond that
+        let mut out = Vec::with_capacity(size.min(compressed.len() * MAX_MATCH / 3));
+        while let Some((&control, rest)) = runs.split_first() {
+            if control & 0x80 == 0 {
+                let len = control as usize + 1;
+                let literals = rest.get(..len).ok_or("a literal run is cut short")?;
+                out.extend_from_slice(literals);
+                runs = &rest[len..];
+            } else {
+                let offset = match rest {
+                    [a, b, ..] => u16::from_be_bytes([*a, *b]) as usize,
+                    _ => return Err("a match is cut short".into()),
+                };
+                if offset == 0 || offset > out.len() {
+                    return Err(
+                        format!("a match points {} bytes back, before the record", offset).into(),
+                    );
+                }
+                let start = out.len() - offset;
+                for i in 0..(control & 0x7f) as usize + MIN_MATCH {
+                    out.push(out[start + i]);
+                }
+                runs = &rest[2..];
+            }
+            if out.len() > size {
+                return Err("the record is longer than its size".into());
+            }
+        }
+
+        if out.len() != size {
+            return Err(format!("the record is {} bytes, not {}", out.len(), size).into());
+        }
+        Ok(out)
+    }
+}
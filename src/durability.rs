@@ -0,0 +1,172 @@
+use std::fs::File;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+use crate::errors::Error;
+
+/// How hard the db works to make sure an acknowledged write survives a power loss. Every mode
+/// survives a crash of the process itself, as writes are handed to the OS before they return.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum Durability {
+    /// Never fsync, leave it to the OS to write the log out. This is the default.
+    #[default]
+    Never,
+
+    /// fsync every write before it returns, an acknowledged write is on stable storage.
+    EveryCommit,
+
+    /// fsync on a background thread once per interval. Up to an interval's worth of acknowledged
+    /// writes can be lost.
+    Interval(Duration),
+
+    /// Like `EveryCommit`, but writers that commit while an fsync is in flight don't each issue
+    /// their own, they wait for the next one together.
+    GroupCommit,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DurabilityStats {
+    /// Number of records written to the log file.
+    pub writes: u64,
+
+    /// Number of those records that are known to be on stable storage.
+    pub synced_writes: u64,
+
+    /// Number of fsyncs issued.
+    pub syncs: u64,
+
+    /// Number of records waiting in the write buffer, these haven't reached the OS yet.
+    pub buffered_writes: u64,
+}
+
+/// Accumulate records in memory and write them to the log in bulk, trading the last few
+/// milliseconds of writes in a crash for throughput. Has no effect with `Durability::EveryCommit`
+/// or `Durability::GroupCommit`, which have to write every commit out before acknowledging it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BufferPolicy {
+    /// Write the buffer out once it holds at least this many bytes.
+    pub max_bytes: usize,
+
+    /// Write the buffer out once its oldest record has waited this long.
+    pub max_age: Duration,
+}
+
+impl Default for BufferPolicy {
+    fn default() -> Self {
+        Self {
+            max_bytes: 64 * 1024,
+            max_age: Duration::from_millis(10),
+        }
+    }
+}
+
+pub trait Durable {
+    /// Writes out any records waiting in the write buffer, see `BufferPolicy`.
+    fn flush(&self) -> Result<(), Error>;
+
+    /// Flushes and fsyncs everything written so far, regardless of the configured `Durability`.
+    fn sync(&self) -> Result<(), Error>;
+
+    fn durability_stats(&self) -> Result<DurabilityStats, Error>;
+
+    /// Holds off fsyncs until the returned guard is dropped, as if one were in flight, so that
+    /// tests can queue up writers behind it.
+    #[doc(hidden)]
+    fn pause_syncs(&self) -> Result<SyncPause, Error>;
+}
+
+/// Returned by `Durable::pause_syncs`, lets the next fsync go ahead when dropped.
+pub struct SyncPause {
+    syncer: Arc<Syncer>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Syncer {
+    state: Mutex<SyncState>,
+    synced: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct SyncState {
+    stats: DurabilityStats,
+    syncing: bool,
+}
+
+impl Syncer {
+    /// Records that `count` records were appended, must be called while the log is still locked
+    /// so that sequence numbers follow the order of the writes. Returns the sequence number of the
+    /// last one.
+    pub(crate) fn wrote(&self, count: u64) -> Result<u64, Error> {
+        let mut state = self.state.lock().map_err(Error::lock_error)?;
+        state.stats.writes += count;
+        Ok(state.stats.writes)
+    }
+
+    /// Everything written so far is on stable storage by other means, like a compaction that
+    /// rewrote and fsynced the whole log.
+    pub(crate) fn synced_all(&self) -> Result<(), Error> {
+        let mut state = self.state.lock().map_err(Error::lock_error)?;
+        state.stats.synced_writes = state.stats.writes;
+        Ok(())
+    }
+
+    /// Takes the turn to fsync, waiting for one in flight, and gives it up when the pause is
+    /// dropped without syncing.
+    pub(crate) fn pause(self: &Arc<Self>) -> Result<SyncPause, Error> {
+        let mut state = self.state.lock().map_err(Error::lock_error)?;
+        while state.syncing {
+            state = self.synced.wait(state).map_err(Error::lock_error)?;
+        }
+        state.syncing = true;
+
+        Ok(SyncPause {
+            syncer: self.clone(),
+        })
+    }
+
+    pub(crate) fn stats(&self) -> Result<DurabilityStats, Error> {
+        Ok(self.state.lock().map_err(Error::lock_error)?.stats)
+    }
+
+    /// Returns once write number `seq` is on stable storage. If another thread is already
+    /// syncing, waits for it and then shares the next fsync with everyone else who queued up in
+    /// the meantime.
+    pub(crate) fn sync_through(&self, seq: u64, file: &File) -> Result<(), Error> {
+        let mut state = self.state.lock().map_err(Error::lock_error)?;
+        loop {
+            if state.stats.synced_writes >= seq {
+                return Ok(());
+            }
+            if !state.syncing {
+                break;
+            }
+            state = self.synced.wait(state).map_err(Error::lock_error)?;
+        }
+
+        state.syncing = true;
+        let target = state.stats.writes;
+        drop(state);
+
+        let result = file.sync_data();
+
+        let mut state = self.state.lock().map_err(Error::lock_error)?;
+        state.syncing = false;
+        if result.is_ok() {
+            state.stats.synced_writes = state.stats.synced_writes.max(target);
+            state.stats.syncs += 1;
+        }
+        self.synced.notify_all();
+
+        result
+            .map_err(|err| Error::OsError(format!("Failed to fsync the log, error: {}", err), err))
+    }
+}
+
+impl Drop for SyncPause {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.syncer.state.lock() {
+            state.syncing = false;
+        }
+        self.syncer.synced.notify_all();
+    }
+}
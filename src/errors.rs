@@ -1,16 +1,38 @@
 use std::fmt::Display;
 use std::io;
+use std::path::Path;
 
 #[derive(Debug)]
 pub enum Error {
     OsError(String, io::Error),
     LogParseError(String, bincode::Error),
+    CorruptLog(String),
+    /// The log was written by a schema with different tables than the one opening it.
+    SchemaMismatch(String),
+    /// A record couldn't be opened by the `Cipher`, because it was sealed with another key or
+    /// tampered with, or the log is sealed and no cipher was given.
+    Decryption(String),
+    /// A compressed record couldn't be decompressed, or the log is compressed by a `Compressor`
+    /// the db wasn't opened with.
+    Decompression(String),
+    /// The log was written by a newer version of hmdb.
+    UnsupportedFormat(String),
+    /// The db is open in another process, whose pid is included if it could be determined.
+    AlreadyOpen(String, Option<u32>),
     LockError(String),
+    /// Returned by `Migration::migrate` when the tables of the previous version of a schema can't
+    /// be converted.
+    MigrationFailed(String),
+    /// The options a db was opened with contradict each other, see `Options::validate`.
+    InvalidOptions(String),
     SerializeError(String, bincode::Error),
+    /// A record was larger than `Options::max_record_bytes`, and wasn't written.
+    RecordTooLarge(String),
 }
 
 impl Error {
-    pub(crate) fn lock_error<E: Display>(e: E) -> Self {
+    #[doc(hidden)]
+    pub fn lock_error<E: Display>(e: E) -> Self {
         Self::LockError(format!(
             "RwLock Poisoned, this indicates that one of your transactions panicked! Error: {}",
             e
@@ -26,4 +48,18 @@ impl Error {
             e,
         )
     }
+
+    pub(crate) fn already_open(path: &Path, pid: Option<u32>) -> Self {
+        let holder = match pid {
+            Some(pid) => format!("process {}", pid),
+            None => "another process".to_string(),
+        };
+        Self::AlreadyOpen(
+            format!(
+                "The db at {:?} is already open in {}, only one process can have it open at a time.",
+                path, holder
+            ),
+            pid,
+        )
+    }
 }
@@ -0,0 +1,293 @@
+//! Framing for the records in the log.
+//!
+//! Every record is written as:
+//!
+//! ```text
+//! | marker: 4 bytes | size: u32 BE | crc32 of payload: u32 BE | payload: size bytes |
+//! ```
+//!
+//! The marker of a record whose payload is compressed differs in its last byte, see `compression`.
+//! So does the marker of a record of more than 4 GiB, whose size is a u64 BE
+//! instead:
+//!
+//! ```text
+//! | wide marker: 4 bytes | size: u64 BE | crc32 of payload: u32 BE | payload: size bytes |
+//! ```
+//!
+//! The marker lets a reader resynchronize after a damaged record by scanning forward for the next
+//! marker whose checksum verifies. Logs written before framing was introduced contain records
+//! that are only prefixed by their size, those are still read, but cannot be verified.
+
+use std::io;
+use std::io::Read;
+
+pub(crate) const RECORD_MARKER: [u8; 4] = [0xdb, 0x1e, 0x5e, 0xc0];
+pub(crate) const COMPRESSED_MARKER: [u8; 4] = [0xdb, 0x1e, 0x5e, 0xc1];
+const WIDE_MARKER: [u8; 4] = [0xdb, 0x1e, 0x5e, 0xc2];
+const WIDE_COMPRESSED_MARKER: [u8; 4] = [0xdb, 0x1e, 0x5e, 0xc3];
+pub(crate) const FRAME_HEADER_LEN: usize = 12;
+const WIDE_HEADER_LEN: usize = 16;
+const LEGACY_HEADER_LEN: usize = 4;
+
+pub(crate) enum Frame<'a> {
+    /// A complete record, `end` is the offset just past it.
+    Record {
+        payload: &'a [u8],
+        compressed: bool,
+        end: usize,
+    },
+    /// A record whose size prefix is not followed by a marker, written by an older version.
+    Legacy { payload: &'a [u8], end: usize },
+    /// The buffer ends before the record does.
+    Torn,
+    /// The record is complete, but its checksum does not match its contents.
+    Corrupt,
+}
+
+pub(crate) fn encode(payload: &[u8], compressed: bool) -> Vec<u8> {
+    let wide = u32::try_from(payload.len()).is_err();
+    let mut frame = Vec::with_capacity(WIDE_HEADER_LEN + payload.len());
+    frame.extend_from_slice(match (wide, compressed) {
+        (false, false) => &RECORD_MARKER,
+        (false, true) => &COMPRESSED_MARKER,
+        (true, false) => &WIDE_MARKER,
+        (true, true) => &WIDE_COMPRESSED_MARKER,
+    });
+    match wide {
+        true => frame.extend_from_slice(&(payload.len() as u64).to_be_bytes()),
+        false => frame.extend_from_slice(&(payload.len() as u32).to_be_bytes()),
+    }
+    frame.extend_from_slice(&crc32(payload).to_be_bytes());
+    frame.extend_from_slice(payload);
+    frame
+}
+
+/// Whether `bytes` start with the marker of a record.
+fn is_marker(bytes: &[u8]) -> bool {
+    [
+        RECORD_MARKER,
+        COMPRESSED_MARKER,
+        WIDE_MARKER,
+        WIDE_COMPRESSED_MARKER,
+    ]
+    .iter()
+    .any(|marker| bytes.starts_with(marker))
+}
+
+/// The length of the header of the record `bytes` start with, and of its payload, if `bytes`
+/// hold all of its header. A size that doesn't fit in memory saturates, so the record is torn.
+/// Bytes that don't start with a marker are only read as a legacy size prefix if `legacy`.
+fn frame_len(bytes: &[u8], legacy: bool) -> Option<(usize, usize)> {
+    let (header_len, size) = match bytes.get(..4)? {
+        marker if marker == RECORD_MARKER || marker == COMPRESSED_MARKER => {
+            (FRAME_HEADER_LEN, read_u32(bytes.get(4..8)?) as u64)
+        }
+        marker if marker == WIDE_MARKER || marker == WIDE_COMPRESSED_MARKER => {
+            (WIDE_HEADER_LEN, read_u64(bytes.get(4..12)?))
+        }
+        prefix if legacy => (LEGACY_HEADER_LEN, read_u32(prefix) as u64),
+        _ => return None,
+    };
+
+    Some((header_len, usize::try_from(size).unwrap_or(usize::MAX)))
+}
+
+/// Reads the record at `index`. Records without a marker are only read if `legacy`, the log is
+/// from before framing was introduced, anywhere else they're damaged, so that a record can't
+/// pass without its checksum.
+pub(crate) fn read_frame(buffer: &[u8], index: usize, legacy: bool) -> Frame<'_> {
+    let remaining = &buffer[index..];
+    if remaining.len() < LEGACY_HEADER_LEN {
+        return Frame::Torn;
+    }
+
+    if !is_marker(remaining) && !legacy {
+        return Frame::Corrupt;
+    }
+    if !is_marker(remaining) {
+        let size = read_u32(&remaining[..4]) as usize;
+        return match remaining.get(LEGACY_HEADER_LEN..LEGACY_HEADER_LEN + size) {
+            Some(payload) => Frame::Legacy {
+                payload,
+                end: index + LEGACY_HEADER_LEN + size,
+            },
+            None => Frame::Torn,
+        };
+    }
+
+    let (header_len, size) = match frame_len(remaining, legacy) {
+        Some(len) => len,
+        None => return Frame::Torn,
+    };
+    if remaining.len() < header_len {
+        return Frame::Torn;
+    }
+
+    // The last bit of the marker
+    let compressed = remaining[3] & 1 == 1;
+    let checksum = read_u32(&remaining[header_len - 4..header_len]);
+    match remaining.get(header_len..header_len.saturating_add(size)) {
+        Some(payload) if crc32(payload) == checksum => Frame::Record {
+            payload,
+            compressed,
+            end: index + header_len + size,
+        },
+        Some(_) => Frame::Corrupt,
+        None => Frame::Torn,
+    }
+}
+
+/// How many bytes a reader reads from its source at a time, at least.
+const WINDOW: usize = 64 * 1024;
+
+/// Reads the records of a log from `source` as they're needed, instead of reading all of it into
+/// memory first. Only the record being read, and whatever follows it in the current window, are
+/// held in memory.
+pub(crate) struct FrameReader<R> {
+    source: R,
+    window: Vec<u8>,
+    /// Offset of the start of the window in the log.
+    base: u64,
+    /// Where in the window the next record starts.
+    pos: usize,
+    eof: bool,
+    /// Whether records without a marker are read, see `read_frame`.
+    legacy: bool,
+}
+
+impl<R: Read> FrameReader<R> {
+    pub(crate) fn new(source: R) -> Self {
+        Self {
+            source,
+            window: vec![],
+            base: 0,
+            pos: 0,
+            eof: false,
+            legacy: false,
+        }
+    }
+
+    /// Reads records without a marker from now on, for a log from before framing was introduced.
+    pub(crate) fn read_legacy(&mut self) {
+        self.legacy = true;
+    }
+
+    /// Offset in the log of the next record.
+    pub(crate) fn offset(&self) -> u64 {
+        self.base + self.pos as u64
+    }
+
+    /// Bytes read from `source` so far.
+    pub(crate) fn bytes_read(&self) -> u64 {
+        self.base + self.window.len() as u64
+    }
+
+    /// The bytes from the next record on that have been read so far.
+    pub(crate) fn rest(&self) -> &[u8] {
+        &self.window[self.pos..]
+    }
+
+    /// Whether `rest` holds the rest of the log.
+    pub(crate) fn at_end(&self) -> bool {
+        self.eof
+    }
+
+    pub(crate) fn advance(&mut self, len: usize) {
+        self.pos = (self.pos + len).min(self.window.len());
+    }
+
+    /// Reads until `rest` holds at least `len` bytes, or the rest of the log.
+    pub(crate) fn fill(&mut self, len: usize) -> io::Result<()> {
+        let available = self.window.len() - self.pos;
+        if available >= len || self.eof {
+            return Ok(());
+        }
+
+        // Bytes before `pos` are never looked at again
+        self.window.drain(..self.pos);
+        self.base += self.pos as u64;
+        self.pos = 0;
+
+        let wanted = (len - available).max(WINDOW) as u64;
+        let read = (&mut self.source)
+            .take(wanted)
+            .read_to_end(&mut self.window)?;
+        self.eof = (read as u64) < wanted;
+
+        Ok(())
+    }
+
+    /// The next record, see `read_frame`, with `end` relative to `offset`. `None` at the end of
+    /// the log.
+    pub(crate) fn frame(&mut self) -> io::Result<Option<Frame<'_>>> {
+        self.fill(WIDE_HEADER_LEN)?;
+        if self.rest().is_empty() {
+            return Ok(None);
+        }
+        if let Some((header_len, size)) = frame_len(self.rest(), self.legacy) {
+            self.fill(header_len.saturating_add(size))?;
+        }
+
+        Ok(Some(read_frame(self.rest(), 0, self.legacy)))
+    }
+
+    /// Skips to the next record after `offset` whose checksum verifies, `false` if there is none,
+    /// and the rest of the log was skipped.
+    pub(crate) fn resync(&mut self) -> io::Result<bool> {
+        loop {
+            self.advance(1);
+            self.fill(RECORD_MARKER.len())?;
+            let rest = self.rest();
+            if rest.len() < RECORD_MARKER.len() {
+                self.advance(rest.len());
+                return Ok(false);
+            }
+            if is_marker(rest) {
+                if let Some(Frame::Record { .. }) = self.frame()? {
+                    return Ok(true);
+                }
+            }
+        }
+    }
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes(bytes.try_into().expect("slice with incorrect length"))
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    u64::from_be_bytes(bytes.try_into().expect("slice with incorrect length"))
+}
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/// CRC-32 (IEEE 802.3), the same checksum used by zip and png.
+pub(crate) const fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    let mut i = 0;
+    while i < data.len() {
+        crc = CRC_TABLE[((crc ^ data[i] as u32) & 0xff) as usize] ^ (crc >> 8);
+        i += 1;
+    }
+    !crc
+}
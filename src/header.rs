@@ -0,0 +1,147 @@
+//! The header at the start of every log.
+//!
+//! ```text
+//! | magic: 8 bytes | format version: u32 BE | size: u32 BE | crc32 of fields: u32 BE | fields: size bytes |
+//! ```
+//!
+//! The fields are bincode encoded `Header`s. Logs written before the header was introduced, format
+//! 0, start directly with their first record, and name tables by their position in `schema!`.
+//! Those are still read, but the schema that wrote them can't be checked.
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::Error;
+use crate::frame::crc32;
+use crate::log::TableInfo;
+
+/// Can't be mistaken for the start of a record: it isn't the record marker, and as a legacy size
+/// prefix it would announce a record of more than 2GiB.
+pub(crate) const MAGIC: [u8; 8] = [0x89, b'h', b'm', b'd', b'b', b'\r', b'\n', 0x1a];
+pub(crate) const FORMAT_VERSION: u32 = 1;
+const FIXED_LEN: usize = 20;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Header {
+    /// `Reader::VERSION` of the schema that wrote the log.
+    pub(crate) version: u32,
+    /// The tables of the schema that wrote the log.
+    pub(crate) tables: Vec<Table>,
+    /// `Codec::NAME` of the codec that encoded the records.
+    pub(crate) codec: String,
+    /// Whether the records were sealed by a `Cipher`.
+    pub(crate) encrypted: bool,
+    /// `Compressor::name` of the compressor that compressed the records, if any were.
+    pub(crate) compressor: Option<String>,
+    /// Orders the files of a log, see `log::LogFiles`. Headerless logs are a single file of
+    /// generation 1.
+    pub(crate) generation: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Table {
+    pub(crate) id: u32,
+    pub(crate) name: String,
+    /// See `fingerprint`.
+    pub(crate) types: String,
+}
+
+pub(crate) enum Parsed {
+    /// `end` is the offset of the first record.
+    Header { header: Header, end: usize },
+    /// Written by a newer version of hmdb, the rest of the header can't be read.
+    Unsupported { version: u32 },
+    /// The log starts with a record, or is empty. A log that starts with a record was written by
+    /// the first version of its schema.
+    Missing,
+    /// The buffer ends before the header does.
+    Torn,
+    /// The header is complete, but its checksum does not match its contents.
+    Corrupt,
+}
+
+/// Table names and key and value types as written in `schema!`, without whitespace so that the
+/// formatting of the invocation doesn't matter.
+pub(crate) fn fingerprint(tables: &str) -> String {
+    tables.split_whitespace().collect()
+}
+
+pub(crate) fn encode(
+    tables: &[TableInfo],
+    version: u32,
+    codec: &str,
+    encrypted: bool,
+    compressor: Option<&str>,
+    generation: u64,
+) -> Result<Vec<u8>, Error> {
+    let header = Header {
+        version,
+        codec: codec.to_string(),
+        encrypted,
+        compressor: compressor.map(str::to_string),
+        generation,
+        tables: tables
+            .iter()
+            .map(|table| Table {
+                id: table.id,
+                name: table.name.to_string(),
+                types: fingerprint(table.types),
+            })
+            .collect(),
+    };
+    let fields = bincode::serialize(&header)
+        .map_err(|err| Error::serialize(std::any::type_name::<Header>(), err))?;
+
+    let mut bytes = Vec::with_capacity(FIXED_LEN + fields.len());
+    bytes.extend_from_slice(&MAGIC);
+    bytes.extend_from_slice(&FORMAT_VERSION.to_be_bytes());
+    bytes.extend_from_slice(&(fields.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(&crc32(&fields).to_be_bytes());
+    bytes.extend_from_slice(&fields);
+    Ok(bytes)
+}
+
+pub(crate) fn read(buffer: &[u8]) -> Parsed {
+    let magic_len = buffer.len().min(MAGIC.len());
+    if buffer.is_empty() || buffer[..magic_len] != MAGIC[..magic_len] {
+        return Parsed::Missing;
+    }
+    if buffer.len() < FIXED_LEN {
+        return Parsed::Torn;
+    }
+
+    let version = read_u32(&buffer[8..12]);
+    if version > FORMAT_VERSION {
+        return Parsed::Unsupported { version };
+    }
+    let size = read_u32(&buffer[12..16]) as usize;
+    let checksum = read_u32(&buffer[16..20]);
+    let fields = match buffer.get(FIXED_LEN..FIXED_LEN + size) {
+        Some(fields) => fields,
+        None => return Parsed::Torn,
+    };
+    if crc32(fields) != checksum {
+        return Parsed::Corrupt;
+    }
+
+    match bincode::deserialize(fields) {
+        Ok(header) => Parsed::Header {
+            header,
+            end: FIXED_LEN + size,
+        },
+        Err(_) => Parsed::Corrupt,
+    }
+}
+
+/// The generation of a file of the log, see `Header::generation`, `None` if it's empty or its
+/// header can't be read.
+pub(crate) fn generation(buffer: &[u8]) -> Option<u64> {
+    match read(buffer) {
+        Parsed::Header { header, .. } => Some(header.generation),
+        Parsed::Missing if !buffer.is_empty() => Some(1),
+        _ => None,
+    }
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes(bytes.try_into().expect("slice with incorrect length"))
+}
@@ -21,12 +21,87 @@
 //! }
 //! ```
 //!
+//! The log file is named after the schema, `SchemaName` here. To rename the schema without
+//! renaming its log, give the log a name of its own:
+//!
+//! ```ignore,rust
+//! hmdb::schema! {
+//!     RenamedSchema as "SchemaName" {
+//!         table1_name: <u8, String>,
+//!         table2_name: <String, u64>
+//!     }
+//! }
+//! ```
+//!
+//! A db can also be opened under a name of its own, with `Options::log_name`, to keep several
+//! logs of one schema in the same directory.
+//!
+//! Keys and values are encoded with bincode, unless the schema chooses another codec with
+//! `#[codec = MyCodec]` before its name, see `codec`.
+//!
+//! Records name their table by an id, derived from the table's name unless it's given one. Tables
+//! can be reordered, added and removed, the records of a removed table are ignored and dropped by
+//! the next compaction. To rename a table without losing its records, give it the id it had, which
+//! is `hmdb::log::table_id("old_name")` if it didn't have an explicit one:
+//!
+//! ```ignore,rust
+//! hmdb::schema! {
+//!     SchemaName {
+//!         #[id = 3]
+//!         documents: <Uuid, Meta>,
+//!         table2_name: <String, u64>
+//!     }
+//! }
+//! ```
+//!
+//! The log also records the key and value types of each table, as they're spelled in `schema!`.
+//! Opening it with a schema that changes the types of a table fails with `Error::SchemaMismatch`,
+//! as does spelling a type differently, `String` as `std::string::String` for example, or giving
+//! a table a different id than it had.
+//!
+//! To change the types of a table, declare a new version of the schema that migrates the old
+//! one's log, see `migration`. When only the value type of a table changes, list the value types
+//! it had instead, oldest first:
+//!
+//! ```ignore,rust
+//! hmdb::schema! {
+//!     SchemaName {
+//!         accounts: <Username, AccountV3> from [AccountV1, AccountV2],
+//!         table2_name: <String, u64>
+//!     }
+//! }
+//! ```
+//!
+//! Records of an earlier type are converted with `AccountV3: From<AccountV1>` and
+//! `AccountV3: From<AccountV2>` as they're read, and rewritten as `AccountV3` when the db is opened
+//! for writing.
+//!
 //! ## Reading your db file
 //!
 //! ```ignore, rust
 //! let db = SchemaName::init("db_dir").unwrap();
 //! ```
 //!
+//! To configure how it's opened, and how it behaves once it is:
+//!
+//! ```ignore, rust
+//! let db = SchemaName::options()
+//!     .sync(Durability::EveryCommit)
+//!     .open("db_dir")
+//!     .unwrap();
+//! ```
+//!
+//! To encrypt the log at rest, open the db with a `Cipher`, see `cipher`. To compress large
+//! records, open it with a `Compression`, see `compression`. The log is kept in a few files named
+//! after it, a snapshot and the segments of the log written since, see `log::LogFiles`.
+//!
+//! Or, without creating or writing anything, for inspecting a db another process has open:
+//!
+//! ```ignore, rust
+//! let db = SchemaName::init_read_only("db_dir").unwrap();
+//! db.refresh().unwrap();
+//! ```
+//!
 //! ## Using your tables
 //!
 //! ```ignore, rust
@@ -57,27 +132,112 @@ macro_rules! head {
     };
 }
 
+#[doc(hidden)]
+#[macro_export]
+macro_rules! log_name {
+    ($schema_name: ident) => {
+        stringify!($schema_name)
+    };
+    ($schema_name: ident, $log_name: literal $(; $previous: path)?) => {
+        $log_name
+    };
+    // Every version of a schema shares the log of the first
+    ($schema_name: ident; $previous: path) => {
+        <$previous as $crate::log::Reader<_, _>>::LOG_NAME
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! schema_version {
+    () => {
+        1
+    };
+    ($previous: path) => {
+        <$previous as $crate::log::Reader<_, _>>::VERSION + 1
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! schema_codec {
+    () => {
+        $crate::codec::Bincode
+    };
+    ($codec: path) => {
+        $codec
+    };
+}
+
+/// Loads a log written by a predecessor of the schema, and migrates it.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! load_previous {
+    ($schema_name: ident, $log: expr, $policy: expr, $cipher: expr, $compressor: expr, $threads: expr) => {};
+    ($schema_name: ident, $log: expr, $policy: expr, $cipher: expr, $compressor: expr, $threads: expr, $previous: path) => {
+        let (previous, recovery) = <$previous as $crate::migration::Versioned>::load(
+            $log,
+            $policy,
+            $cipher,
+            $compressor,
+            $threads,
+        )?;
+        return Ok((
+            <$schema_name as $crate::migration::Migration>::migrate(previous)?,
+            recovery,
+        ));
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! table_id {
+    ($table_name: ident) => {
+        $crate::log::table_id(stringify!($table_name))
+    };
+    ($table_name: ident, $id: literal) => {
+        $id
+    };
+}
+
+#[doc(hidden)]
+pub use bincode;
+
 #[macro_export]
 macro_rules! schema {
-    ($schema_name:ident {
-        $($table_name: ident: <$table_key: ty, $table_value: ty>),+
+    ($(#[codec = $codec:path])? $schema_name:ident $(as $log_name:literal)? $(from $previous:path)? {
+        $($(#[id = $table_id: literal])? $table_name: ident: <$table_key: ty, $table_value: ty> $(from [$($table_history: ty),+])?),+
     }) => {
 
         use std::collections::HashMap;
         use $crate::log::{TableEvent, Reader, SchemaEvent, Writer, LogCompacter};
-        use $crate::table::Table;
+        use $crate::durability::{Durable, DurabilityStats};
+        use $crate::lock::DbLock;
+        use $crate::options::Options;
+        use $crate::recovery::{RecoveryPolicy, RecoveryReport};
+        use $crate::log::ReadOnlyLog;
+        use $crate::table::{ReadOnlyTable, Table};
         use std::path::Path;
-        use std::thread;
-        use tracing::error;
-        use std::thread::JoinHandle;
         use std::time::Duration;
 
         #[derive(Clone, Debug)]
         pub struct $schema_name {
             incomplete_write: bool,
+            recovery: RecoveryReport,
+            writer: Writer,
             $(pub $table_name: Table<$table_key, $table_value, helper_log::$table_name>),*
         }
 
+        /// The tables as plain maps, which `Migration::migrate` converts between versions.
+        pub mod tables {
+            use super::*;
+
+            #[derive(Default)]
+            pub struct $schema_name {
+                $(pub $table_name: HashMap<$table_key, $table_value>),*
+            }
+        }
+
         pub mod transaction {
             use super::*;
             use $crate::transaction::TransactionTable;
@@ -87,15 +247,215 @@ macro_rules! schema {
             }
         }
 
+        pub mod read_only {
+            use super::*;
+
+            /// A db opened with `init_read_only`, which can be read and refreshed, but not written.
+            #[derive(Clone, Debug)]
+            pub struct $schema_name {
+                pub(super) recovery: RecoveryReport,
+                pub(super) log: ReadOnlyLog,
+                $(pub $table_name: ReadOnlyTable<$table_key, $table_value>),*
+            }
+
+            impl $schema_name {
+                pub fn incomplete_write(&self) -> bool {
+                    self.log.incomplete_write()
+                }
+
+                pub fn recovery_report(&self) -> &RecoveryReport {
+                    &self.recovery
+                }
+
+                /// Picks up whatever was written to the db since it was opened or last refreshed.
+                pub fn refresh(&self) -> Result<(), $crate::errors::Error> {
+                    self.log.refresh()
+                }
+            }
+        }
+
         mod helper_disk {
             use super::*;
-            use $crate::log::TableEvent;
+            use $crate::log::{Replay, ReplayFrom, TableEvent};
+            use std::sync::{RwLock, Weak};
 
+            /// Encoded as the id of its table followed by the length prefixed event, so that the
+            /// records of tables the schema doesn't have can be skipped.
             #[allow(non_camel_case_types)]
-            #[derive(serde::Serialize, serde::Deserialize)]
             pub enum $schema_name {
+                $($table_name(TableEvent<$table_key, $table_value>),)*
+                /// A record of a table that was removed from the schema.
+                __unknown_table,
+            }
+
+            pub type SchemaCodec = $crate::schema_codec!($($codec)?);
+
+            impl serde::Serialize for $schema_name {
+                fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    use serde::ser::Error;
+                    let (id, event) = match self {
+                        $($schema_name::$table_name(event) => ($crate::table_id!($table_name $(, $table_id)?), <SchemaCodec as $crate::codec::Codec>::encode(event)),)*
+                        $schema_name::__unknown_table => return Err(S::Error::custom("records of unknown tables are never written")),
+                    };
+                    serde::Serialize::serialize(&(id, event.map_err(S::Error::custom)?), serializer)
+                }
+            }
+
+            impl<'de> serde::Deserialize<'de> for $schema_name {
+                fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                    use serde::de::Error;
+                    let (id, event): (u32, Vec<u8>) = serde::Deserialize::deserialize(deserializer)?;
+                    decode_event(id, &event, &[]).map_err(D::Error::custom)
+                }
+            }
+
+            /// Decodes the event of a record of the table `id`, see `Reader::decode`.
+            pub fn decode_event(id: u32, event: &[u8], upcasts: &[(u32, usize)]) -> Result<$schema_name, $crate::codec::CodecError> {
+                use $crate::codec::Codec;
+                let earlier = upcasts.iter().find(|(table, _)| *table == id).map(|(_, index)| *index);
+                $(
+                    if id == $crate::table_id!($table_name $(, $table_id)?) {
+                        let history: &[fn(&[u8]) -> Result<TableEvent<$table_key, $table_value>, $crate::codec::CodecError>] = &[$($(
+                            |event| SchemaCodec::decode::<TableEvent<$table_key, $table_history>>(event).map(TableEvent::upcast),
+                        )+)?];
+                        let event = match earlier.and_then(|index| history.get(index)) {
+                            Some(decode) => decode(event),
+                            None => SchemaCodec::decode(event),
+                        };
+                        return event.map($schema_name::$table_name);
+                    }
+                )*
+                Ok($schema_name::__unknown_table)
+            }
+
+            /// How the records of headerless logs named their table, by its position in `schema!`.
+            #[allow(non_camel_case_types)]
+            #[derive(serde::Deserialize)]
+            pub enum Baseline {
                 $($table_name(TableEvent<$table_key, $table_value>)),*
             }
+
+            impl From<Baseline> for $schema_name {
+                fn from(entry: Baseline) -> Self {
+                    match entry {
+                        $(Baseline::$table_name(event) => $schema_name::$table_name(event)),*
+                    }
+                }
+            }
+
+            pub fn apply(entry: $schema_name, $($table_name: &mut HashMap<$table_key, $table_value>),*) {
+                match entry {
+                    $($schema_name::$table_name(event) => event.apply($table_name),)*
+                    $schema_name::__unknown_table => {}
+                };
+            }
+
+            /// Events of each table, on their way to the thread that applies them, see
+            /// `apply_on_threads`.
+            #[derive(Default)]
+            pub struct Batches {
+                $($table_name: Vec<TableEvent<$table_key, $table_value>>),*
+            }
+
+            /// Fills each table on a thread of its own with the entries `parse` hands out, in the
+            /// order it hands them out, see `Versioned::load`.
+            pub fn apply_on_threads<F>(tables: &mut tables::$schema_name, parse: F) -> Result<RecoveryReport, $crate::errors::Error>
+            where
+                F: FnOnce(&mut dyn FnMut($schema_name)) -> Result<RecoveryReport, $crate::errors::Error>,
+            {
+                const BATCH: usize = 1024;
+                std::thread::scope(|scope| {
+                    $(
+                        let (sender, receiver) = std::sync::mpsc::sync_channel::<Vec<TableEvent<$table_key, $table_value>>>(4);
+                        let table = &mut tables.$table_name;
+                        scope.spawn(move || {
+                            for events in receiver {
+                                events.into_iter().for_each(|event| event.apply(table));
+                            }
+                        });
+                        let $table_name = sender;
+                    )*
+
+                    // A send only fails if the table's thread panicked, which the scope re-raises
+                    let mut batches = Batches::default();
+                    let report = parse(&mut |entry| match entry {
+                        $(
+                            $schema_name::$table_name(event) => {
+                                batches.$table_name.push(event);
+                                if batches.$table_name.len() >= BATCH {
+                                    let _ = $table_name.send(std::mem::take(&mut batches.$table_name));
+                                }
+                            }
+                        )*
+                        $schema_name::__unknown_table => {}
+                    });
+                    $(let _ = $table_name.send(batches.$table_name);)*
+
+                    report
+                })
+            }
+
+            /// Every entry of every table, as the records of a snapshot. Read with the log locked
+            /// for writing, so that they're a consistent view.
+            pub fn entries(db: &super::$schema_name) -> Result<Vec<$schema_name>, $crate::errors::Error> {
+                let mut data = vec![];
+                $(
+                    for (key, val) in db.$table_name.get_all()? {
+                        data.push(helper_log::$table_name::insert(key, val));
+                    }
+                )*
+
+                Ok(data)
+            }
+
+            /// Every entry of `tables`, as the records of a snapshot, see `Sealed::compact`.
+            pub fn snapshot(tables: tables::$schema_name) -> Vec<$schema_name> {
+                let mut data = vec![];
+                $(
+                    for (key, val) in tables.$table_name {
+                        data.push(helper_log::$table_name::insert(key, val));
+                    }
+                )*
+
+                data
+            }
+
+            /// Replaces every file of the log with a snapshot, so that none is left in an old
+            /// format, see `WriteGuard::compact_log`.
+            pub fn rewrite(db: &super::$schema_name) -> Result<(), $crate::errors::Error> {
+                let log = db.writer.begin_write()?;
+                let data = entries(db)?;
+                log.compact_log(data)
+            }
+
+            /// Applies records written to the log after it was loaded. Only holds weak references
+            /// to the tables, see `Writer::on_replay`.
+            pub fn replay($($table_name: Weak<RwLock<HashMap<$table_key, $table_value>>>),*) -> Box<Replay> {
+                Box::new(move |bytes, from, cipher, compressor| {
+                    $(
+                        let $table_name = match $table_name.upgrade() {
+                            Some(table) => table,
+                            None => return Ok(bytes.len() as u64),
+                        };
+                    )*
+                    $(let mut $table_name = $table_name.write().map_err($crate::errors::Error::lock_error)?;)*
+
+                    let policy = RecoveryPolicy::default();
+                    let (log, report) = if from == ReplayFrom::Appended {
+                        <super::$schema_name as Reader<$schema_name, super::$schema_name>>::parse_appended(bytes, &policy, cipher, compressor)?
+                    } else {
+                        <super::$schema_name as Reader<$schema_name, super::$schema_name>>::parse_records(bytes, &policy, cipher, compressor)?
+                    };
+                    if from == ReplayFrom::Reset {
+                        $($table_name.clear();)*
+                    }
+                    for entry in log {
+                        apply(entry, $(&mut $table_name),*);
+                    }
+
+                    Ok(report.valid_bytes)
+                })
+            }
         }
 
         pub mod helper_log {
@@ -122,65 +482,199 @@ macro_rules! schema {
         })*
 
         impl Reader<helper_disk::$schema_name, $schema_name> for $schema_name {
-            fn init<P: AsRef<Path>>(path: P) -> Result<Self, $crate::errors::Error> {
-                let (mut file, schema_path) = Self::open_log(&path)?;
-                let (log, incomplete_write) = Self::parse_log(&mut file)?;
-                let writer = Writer::init(file, schema_path);
-                $(let mut $table_name: HashMap<$table_key, $table_value> = HashMap::new();)*
-                for entry in log {
-                    match entry {
-                        $(
-                            helper_disk::$schema_name::$table_name(TableEvent::Insert(k, v)) => { $table_name.insert(k, v); }
-                            helper_disk::$schema_name::$table_name(TableEvent::Delete(k)) => { $table_name.remove(&k); }
-                            helper_disk::$schema_name::$table_name(TableEvent::Clear) => { $table_name.clear(); }
-                        ),*
-                    };
+            const LOG_NAME: &'static str = $crate::log_name!($schema_name $(, $log_name)? $(; $previous)?);
+            const TABLE_INFO: &'static [$crate::log::TableInfo] = &[$($crate::log::TableInfo {
+                id: $crate::table_id!($table_name $(, $table_id)?),
+                name: stringify!($table_name),
+                types: stringify!(<$table_key, $table_value>),
+                history: &[$($(stringify!(<$table_key, $table_history>)),+)?],
+            }),+];
+            const VERSION: u32 = $crate::schema_version!($($previous)?);
+            const CODEC: &'static str = <helper_disk::SchemaCodec as $crate::codec::Codec>::NAME;
+            type ReadOnly = read_only::$schema_name;
+
+            fn init_with<P: AsRef<Path>>(path: P, options: Options) -> Result<Self, $crate::errors::Error> {
+                options.validate()?;
+                let started = std::time::Instant::now();
+                let (file, schema_path) = Self::open_log(&path, &options)?;
+                let compressor = options.compression.as_ref().map(|compression| compression.compressor.clone());
+                let lock = DbLock::open(&schema_path, options.locking)?;
+
+                // Keep other processes from appending while we read, and possibly truncate, the log
+                let writes = lock.lock_writes()?;
+                let (mut file, files) = $crate::log::read_files(file, &schema_path, true)?;
+                let (tables, mut recovery) = <Self as $crate::migration::Versioned>::load(&files, &options.recovery, options.cipher.as_deref(), compressor.as_deref(), options.replay_threads)?;
+                let generation = files.generation;
+                drop(files);
+                Self::truncate_torn_tail(&mut file, &schema_path, &mut recovery, &options.recovery)?;
+                recovery.table_entries = vec![$((stringify!($table_name).to_string(), tables.$table_name.len())),*];
+                let migrate = recovery.schema_version < Self::VERSION;
+                let upgrade = recovery.format_version < $crate::log::FORMAT_VERSION
+                    || !recovery.upcast_tables.is_empty()
+                    || recovery.encrypted != options.cipher.is_some()
+                    || recovery.compressor.as_deref() != compressor.as_ref().map(|compressor| compressor.name());
+                if upgrade && !migrate && !recovery.skipped.is_empty() {
+                    recovery.kept_copies = $crate::log::keep_copy(&schema_path, &format!("format-{}", recovery.format_version))?;
                 }
+                let schema = $crate::log::LogSchema {
+                    tables: Self::TABLE_INFO,
+                    version: Self::VERSION,
+                    codec: Self::CODEC,
+                };
+                let writer = Writer::init(file, schema_path.clone(), lock.clone(), &options, schema, generation)?;
+                drop(writes);
+
+                let mut db = Self {
+                    incomplete_write: recovery.truncated_bytes > 0,
+                    $($table_name: Table::init(tables.$table_name, writer.clone()),)*
+                    recovery,
+                    writer,
+                };
 
-                Ok(
-                    Self {
-                        incomplete_write,
-                        $($table_name: Table::init($table_name, writer.clone())),*
-                    }
-                )
+                db.writer.on_replay(helper_disk::replay($(db.$table_name.downgrade()),*));
+                if migrate {
+                    $crate::migration::with_backup(&schema_path, db.recovery.schema_version, || helper_disk::rewrite(&db))?;
+                } else if upgrade {
+                    helper_disk::rewrite(&db)?;
+                }
+                db.recovery.elapsed = started.elapsed();
+
+                Ok(db)
+            }
+
+            fn init_read_only_with<P: AsRef<Path>>(path: P, options: Options) -> Result<read_only::$schema_name, $crate::errors::Error> {
+                options.validate()?;
+                let started = std::time::Instant::now();
+                let (file, schema_path) = Self::open_log_read_only(&path, &options)?;
+                let compressor = options.compression.as_ref().map(|compression| compression.compressor.clone());
+                let (file, files) = $crate::log::read_files(file, &schema_path, false)?;
+                let (tables, mut recovery) = <Self as $crate::migration::Versioned>::load(&files, &options.recovery, options.cipher.as_deref(), compressor.as_deref(), options.replay_threads)?;
+                let generation = files.generation;
+                drop(files);
+                recovery.table_entries = vec![$((stringify!($table_name).to_string(), tables.$table_name.len())),*];
+                let log_reader = ReadOnlyLog::init(file, schema_path, recovery.valid_bytes, generation, options.cipher.clone(), compressor)?;
+
+                let mut db = read_only::$schema_name {
+                    $($table_name: ReadOnlyTable::init(tables.$table_name),)*
+                    recovery,
+                    log: log_reader,
+                };
+                // Records appended to a log that hasn't been rewritten yet are in the old types, or
+                // aren't sealed the way `options.cipher` expects
+                if db.recovery.schema_version == Self::VERSION
+                    && db.recovery.upcast_tables.is_empty()
+                    && db.recovery.encrypted == options.cipher.is_some()
+                {
+                    db.log.on_replay(helper_disk::replay($(db.$table_name.downgrade()),*));
+                }
+                db.recovery.elapsed = started.elapsed();
+
+                Ok(db)
+            }
+
+            fn decode(data: &[u8], format: u32, upcasts: &[(u32, usize)]) -> Result<$crate::log::LogItems<helper_disk::$schema_name>, $crate::bincode::Error> {
+                if format == 0 {
+                    let items: $crate::log::LogItems<helper_disk::Baseline> = $crate::bincode::deserialize(data)?;
+                    return Ok(items.map(helper_disk::$schema_name::from));
+                }
+                if upcasts.is_empty() {
+                    return $crate::bincode::deserialize(data);
+                }
+                let items: $crate::log::LogItems<(u32, Vec<u8>)> = $crate::bincode::deserialize(data)?;
+                items.try_map(|(id, event)| {
+                    helper_disk::decode_event(id, &event, upcasts).map_err(<$crate::bincode::Error as serde::de::Error>::custom)
+                })
             }
 
             fn incomplete_write(&self) -> bool {
                 self.incomplete_write
             }
+
+            fn recovery_report(&self) -> &RecoveryReport {
+                &self.recovery
+            }
+
+            fn refresh(&self) -> Result<(), $crate::errors::Error> {
+                self.writer.refresh()
+            }
+        }
+
+        impl $crate::migration::Versioned for $schema_name {
+            type Tables = tables::$schema_name;
+
+            fn load(log: &$crate::log::LogFiles, policy: &RecoveryPolicy, cipher: Option<&dyn $crate::cipher::Cipher>, compressor: Option<&dyn $crate::compression::Compressor>, threads: usize) -> Result<(tables::$schema_name, RecoveryReport), $crate::errors::Error> {
+                let version = $crate::migration::schema_version(log.oldest().head());
+                if version.filter(|version| *version < <Self as Reader<helper_disk::$schema_name, $schema_name>>::VERSION).is_some() {
+                    $crate::load_previous!($schema_name, log, policy, cipher, compressor, threads $(, $previous)?);
+                }
+
+                let mut tables = tables::$schema_name::default();
+                let parse = |apply: &mut dyn FnMut(helper_disk::$schema_name)| {
+                    <Self as Reader<helper_disk::$schema_name, $schema_name>>::parse_files(log, policy, cipher, compressor, threads, apply)
+                };
+                let recovery = match threads > 1 {
+                    true => helper_disk::apply_on_threads(&mut tables, parse)?,
+                    false => parse(&mut |entry| helper_disk::apply(entry, $(&mut tables.$table_name),*))?,
+                };
+
+                Ok((tables, recovery))
+            }
+        }
+
+        const _: () = $crate::log::assert_unique_ids(<$schema_name as Reader<helper_disk::$schema_name, $schema_name>>::TABLE_INFO);
+
+        impl Durable for $schema_name {
+            fn flush(&self) -> Result<(), $crate::errors::Error> {
+                self.writer.flush()
+            }
+
+            fn sync(&self) -> Result<(), $crate::errors::Error> {
+                self.writer.sync()
+            }
+
+            fn durability_stats(&self) -> Result<DurabilityStats, $crate::errors::Error> {
+                self.writer.durability_stats()
+            }
+
+            fn pause_syncs(&self) -> Result<$crate::durability::SyncPause, $crate::errors::Error> {
+                self.writer.pause_syncs()
+            }
         }
 
         impl LogCompacter for $schema_name {
             fn compact_log(&self) -> Result<(), $crate::errors::Error> {
-                $(let ($table_name, writer) = self.$table_name.begin_transaction()?;)*
+                let sealed = self.writer.begin_write()?.seal()?;
 
-                let mut data = vec![];
-                $(
-                    for (key, val) in $table_name.get_all() {
-                        data.push(helper_log::$table_name::insert(key.clone(), val.clone()));
-                    }
-                )*
+                // Records the db skipped when it was opened are skipped again, not refused
+                let policy = RecoveryPolicy {
+                    salvage: !self.recovery.skipped.is_empty(),
+                    ..Default::default()
+                };
+                sealed.compact(|files, cipher, compressor| {
+                    let (tables, _) = <Self as $crate::migration::Versioned>::load(files, &policy, cipher, compressor, 1)?;
+                    Ok(helper_disk::snapshot(tables))
+                })
+            }
 
-                writer.compact_log(data)?;
+            fn rotate_cipher(&self, cipher: Option<std::sync::Arc<dyn $crate::cipher::Cipher>>) -> Result<(), $crate::errors::Error> {
+                let log = self.writer.begin_write()?;
+                let data = helper_disk::entries(self)?;
 
-                Ok(())
+                log.rotate_cipher(data, cipher)
             }
 
-            fn start_background_compacter(&self, time_between_compacts: Duration) -> Result<JoinHandle<$crate::errors::Error>, $crate::errors::Error> {
-                let schema = self.clone();
-
-                let join_handle = thread::spawn(move || {
-                    loop {
-                        thread::sleep(time_between_compacts);
+            fn log_stats(&self) -> Result<$crate::compaction::LogStats, $crate::errors::Error> {
+                self.writer.log_stats()
+            }
 
-                        if let Err(err) = schema.compact_log() {
-                            error!("failed to compact log in background compacter: {:?}", err);
-                            return err;
-                        }
-                    }
-                });
+            fn start_background_compacter(&self, time_between_compacts: Duration) -> Result<$crate::compaction::CompacterHandle, $crate::errors::Error> {
+                let writer = self.writer.clone();
 
-                Ok(join_handle)
+                $crate::compaction::CompacterHandle::spawn(
+                    Box::new(self.clone()),
+                    Box::new(move || writer.compaction_due()),
+                    time_between_compacts,
+                )
             }
         }
 
@@ -189,7 +683,8 @@ macro_rules! schema {
              where
                 F: for<'a> FnOnce(&'a mut transaction::$schema_name<'b>) -> Out,
              {
-                $(let ($table_name, writer) = self.$table_name.begin_transaction()?;)*
+                let log = self.writer.begin_write()?;
+                $(let $table_name = self.$table_name.begin_transaction()?;)*
 
                 let mut db = transaction::$schema_name {
                     $($table_name: $table_name,)*
@@ -197,17 +692,38 @@ macro_rules! schema {
 
                 let ret = tx(&mut db);
                 let mut result = vec![];
-                $(result.extend(db.$table_name.pending);)*
-
-                writer.append_all(result)?;
+                $(result.extend(std::mem::take(&mut db.$table_name.pending));)*
+
+                // The tables aren't changed unless the transaction is logged
+                let written = match log.write_all(result) {
+                    Ok(written) => written,
+                    Err(err) => {
+                        $(db.$table_name.rollback();)*
+                        return Err(err);
+                    }
+                };
+                // They aren't held while it's synced either, so it can be grouped
+                drop(db);
+                written.sync()?;
                 Ok(ret)
             }
         }
     }
 }
 
+pub mod cipher;
+pub mod codec;
+pub mod compaction;
+pub mod compression;
+pub mod durability;
 pub mod errors;
+mod frame;
+mod header;
+pub mod lock;
 pub mod log;
+pub mod migration;
+pub mod options;
+pub mod recovery;
 pub mod table;
 pub mod transaction;
 
@@ -223,6 +739,4 @@ impl<V> Value for V where V: Clone {}
 // TODO: The experience of using `String` as your key is somewhat bad, it complains you provided an
 //       &str when it expected &String
 
-// TODO: Have schema migration tests and examples
-
 // TODO: More macro hygiene, see schema_tests::start_empty
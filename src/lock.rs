@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, Weak};
+
+use crate::errors::Error;
+use crate::log::with_suffix;
+
+/// How a db coordinates with other processes that open the same directory.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Locking {
+    /// Only one process can have the db open at a time, others get `Error::AlreadyOpen`. This is
+    /// the default.
+    #[default]
+    Exclusive,
+
+    /// Any number of processes can have the db open. Every write takes an inter-process lock and
+    /// first replays whatever other processes appended since this process last looked, and
+    /// `Reader::refresh` does the same for reads. Writes can't be buffered in this mode.
+    Shared,
+}
+
+/// An advisory lock on a file next to the log, held for as long as the db is open, so that
+/// processes that open the same db can't step on each other. In `Locking::Shared`, handles opened
+/// within the same process share the lock, and keep up with each other the way processes do. In
+/// `Locking::Exclusive`, a second handle would append to the log without seeing what the first
+/// one wrote, so it gets `Error::AlreadyOpen`, the first one has to be cloned or dropped instead.
+#[derive(Debug)]
+pub struct DbLock {
+    _file: File,
+    path: PathBuf,
+    locking: Locking,
+    /// Locked around every write in `Locking::Shared`, the mutex orders handles within this
+    /// process, which share the file and so don't exclude each other through it
+    writes: Option<(Mutex<()>, File)>,
+}
+
+/// Held while a db in `Locking::Shared` is writing to the log.
+#[derive(Debug)]
+pub struct WriteLock<'a> {
+    held: Option<(MutexGuard<'a, ()>, &'a File)>,
+}
+
+/// Locks taken by this process, by the path of the lock file.
+fn held() -> &'static Mutex<HashMap<PathBuf, Weak<DbLock>>> {
+    static HELD: OnceLock<Mutex<HashMap<PathBuf, Weak<DbLock>>>> = OnceLock::new();
+    HELD.get_or_init(Default::default)
+}
+
+impl DbLock {
+    /// Takes the lock for the log at `log_path`, failing with `Error::AlreadyOpen` if another
+    /// process holds it in a way that conflicts with `locking`.
+    pub fn open<P: AsRef<Path>>(log_path: P, locking: Locking) -> Result<Arc<Self>, Error> {
+        let path = lock_path(log_path.as_ref())?;
+
+        let mut held = held().lock().map_err(Error::lock_error)?;
+        held.retain(|_, lock| lock.strong_count() > 0);
+        if let Some(lock) = held.get(&path).and_then(Weak::upgrade) {
+            if lock.locking != locking || locking == Locking::Exclusive {
+                return Err(Error::AlreadyOpen(
+                    format!(
+                        "The db at {:?} is already open in this process with {:?} locking, clone \
+                        that handle instead of opening another.",
+                        log_path.as_ref(),
+                        lock.locking
+                    ),
+                    Some(process::id()),
+                ));
+            }
+            return Ok(lock);
+        }
+
+        let mut file = open_lock_file(&path)?;
+
+        let locked = match locking {
+            Locking::Exclusive => file.try_lock(),
+            Locking::Shared => file.try_lock_shared(),
+        };
+        match locked {
+            Ok(()) => {}
+            Err(std::fs::TryLockError::WouldBlock) => {
+                let mut contents = String::new();
+                let pid = file
+                    .read_to_string(&mut contents)
+                    .ok()
+                    .and_then(|_| contents.trim().parse().ok());
+                return Err(Error::already_open(log_path.as_ref(), pid));
+            }
+            Err(std::fs::TryLockError::Error(err)) => {
+                return Err(Error::OsError(
+                    format!("Failed to lock {:?}, error: {}", path, err),
+                    err,
+                ))
+            }
+        }
+
+        // Purely informational, for the error a second opener gets. Nobody holds the lock alone
+        // in shared mode, so there's no single pid to report.
+        file.set_len(0)
+            .and_then(|_| match locking {
+                Locking::Exclusive => write!(file, "{}", process::id()),
+                Locking::Shared => Ok(()),
+            })
+            .map_err(|err| {
+                Error::OsError(
+                    format!("Failed to write our pid to {:?}, error: {}", path, err),
+                    err,
+                )
+            })?;
+
+        let writes = match locking {
+            Locking::Exclusive => None,
+            Locking::Shared => Some((
+                Mutex::new(()),
+                open_lock_file(&path.with_extension("write-lock"))?,
+            )),
+        };
+
+        let lock = Arc::new(Self {
+            _file: file,
+            path: path.clone(),
+            locking,
+            writes,
+        });
+        held.insert(path, Arc::downgrade(&lock));
+
+        Ok(lock)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn locking(&self) -> Locking {
+        self.locking
+    }
+
+    /// Blocks until no other process is writing to the log. A no-op in `Locking::Exclusive`, where
+    /// no other process can be.
+    pub fn lock_writes(&self) -> Result<WriteLock<'_>, Error> {
+        let (mutex, file) = match &self.writes {
+            Some(writes) => writes,
+            None => return Ok(WriteLock { held: None }),
+        };
+
+        let guard = mutex.lock().map_err(Error::lock_error)?;
+        file.lock().map_err(|err| {
+            Error::OsError(
+                format!("Failed to lock the log for writing, error: {}", err),
+                err,
+            )
+        })?;
+
+        Ok(WriteLock {
+            held: Some((guard, file)),
+        })
+    }
+}
+
+impl Drop for WriteLock<'_> {
+    fn drop(&mut self) {
+        if let Some((_, file)) = &self.held {
+            // Closing the file would release it too, but the file lives as long as the db
+            let _ = file.unlock();
+        }
+    }
+}
+
+fn open_lock_file(path: &Path) -> Result<File, Error> {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)
+        .map_err(|err| {
+            Error::OsError(
+                format!("Failed to open the lock file {:?}, error: {}", path, err),
+                err,
+            )
+        })
+}
+
+fn lock_path(log_path: &Path) -> Result<PathBuf, Error> {
+    let log_path = match (log_path.parent(), log_path.file_name()) {
+        (Some(dir), Some(name)) => dir
+            .canonicalize()
+            .map_err(|err| {
+                Error::OsError(
+                    format!(
+                        "Failed to resolve the db directory {:?}, error: {}",
+                        dir, err
+                    ),
+                    err,
+                )
+            })?
+            .join(name),
+        _ => log_path.to_path_buf(),
+    };
+
+    Ok(with_suffix(&log_path, "lock"))
+}
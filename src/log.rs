@@ -1,14 +1,37 @@
+use crate::cipher::Cipher;
+use crate::codec::{Bincode, Codec};
+use crate::compaction::{CompacterHandle, CompactionPolicy, LogStats};
+use crate::compression::{self, Compression, Compressor, Lz};
+use crate::durability::{BufferPolicy, Durability, DurabilityStats, SyncPause, Syncer};
 use crate::errors::Error;
+use crate::frame;
+use crate::frame::{Frame, FrameReader};
+use crate::header;
+use crate::header::Parsed;
+use crate::lock::{DbLock, Locking, WriteLock};
+use crate::migration;
+use crate::options;
+use crate::options::Options;
+use crate::recovery::{RecoveryPolicy, RecoveryReport, ReplayProgress, PROGRESS_BYTES};
 use crate::{Key, Value};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 use std::fs;
 use std::fs::{File, OpenOptions};
-use std::io::{Read, Write};
+use std::io;
+use std::io::{BufReader, ErrorKind, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex};
-use std::thread::JoinHandle;
-use std::time::Duration;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{error, warn};
+
+/// The format version of the logs this version of hmdb writes. Headerless logs, format 0, are
+/// still read, and rewritten in this one when the db is opened for writing.
+pub const FORMAT_VERSION: u32 = header::FORMAT_VERSION;
 
 #[derive(serde::Serialize, serde::Deserialize)]
 pub enum LogItems<S> {
@@ -16,6 +39,60 @@ pub enum LogItems<S> {
     Batch(Vec<S>),
 }
 
+impl<S> LogItems<S> {
+    pub fn map<T, F: FnMut(S) -> T>(self, mut f: F) -> LogItems<T> {
+        match self {
+            LogItems::Single(item) => LogItems::Single(f(item)),
+            LogItems::Batch(items) => LogItems::Batch(items.into_iter().map(f).collect()),
+        }
+    }
+
+    pub fn try_map<T, E, F: FnMut(S) -> Result<T, E>>(self, mut f: F) -> Result<LogItems<T>, E> {
+        Ok(match self {
+            LogItems::Single(item) => LogItems::Single(f(item)?),
+            LogItems::Batch(items) => {
+                LogItems::Batch(items.into_iter().map(f).collect::<Result<_, _>>()?)
+            }
+        })
+    }
+}
+
+/// A table as declared in `schema!`. Records name their table by `id`, so tables can be
+/// reordered, added and removed without disturbing the records of the others.
+#[derive(Clone, Copy, Debug)]
+pub struct TableInfo {
+    /// Given as `#[id = 3]` in `schema!`, or derived from the name of the table, see `table_id`.
+    pub id: u32,
+    pub name: &'static str,
+    /// The key and value types as written in `schema!`.
+    pub types: &'static str,
+    /// The key and earlier value types, oldest first, from `<K, V> from [V1, V2]` in `schema!`.
+    /// Records of an earlier value type are converted with `V: From<V1>` when they're read.
+    pub history: &'static [&'static str],
+}
+
+/// The id of a table that wasn't given one in `schema!`, the crc32 of its name. Renaming such a
+/// table changes its id, give it the old id explicitly to keep its records.
+pub const fn table_id(name: &str) -> u32 {
+    frame::crc32(name.as_bytes())
+}
+
+/// Fails the build of a schema with two tables sharing an id.
+#[doc(hidden)]
+pub const fn assert_unique_ids(tables: &[TableInfo]) {
+    let mut i = 0;
+    while i < tables.len() {
+        let mut j = i + 1;
+        while j < tables.len() {
+            if tables[i].id == tables[j].id {
+                panic!("two tables of the schema have the same id");
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+}
+
 pub trait SchemaEvent<K: Key, V: Value> {
     type LogEntry: Serialize;
 
@@ -31,184 +108,2568 @@ pub enum TableEvent<K: Key, V: Value> {
     Clear,
 }
 
-pub trait Reader<OnDisk: DeserializeOwned, InMemory> {
-    fn open_log<P>(dir: P) -> Result<(File, PathBuf), Error>
+impl<K: Key, V: Value> TableEvent<K, V> {
+    /// Converts an event of an earlier value type of the table, see `TableInfo::history`.
+    pub fn upcast<W: Value + From<V>>(self) -> TableEvent<K, W> {
+        match self {
+            TableEvent::Insert(k, v) => TableEvent::Insert(k, v.into()),
+            TableEvent::Delete(k) => TableEvent::Delete(k),
+            TableEvent::Clear => TableEvent::Clear,
+        }
+    }
+
+    pub fn apply(self, table: &mut HashMap<K, V>) {
+        match self {
+            TableEvent::Insert(k, v) => {
+                table.insert(k, v);
+            }
+            TableEvent::Delete(k) => {
+                table.remove(&k);
+            }
+            TableEvent::Clear => table.clear(),
+        }
+    }
+}
+
+pub trait Reader<OnDisk: DeserializeOwned + Send, InMemory> {
+    /// The name of the log file, by default the name of the schema, see `schema!`.
+    const LOG_NAME: &'static str;
+
+    /// Recorded in the header of the log, so that a log can't be opened by a schema that gives one
+    /// of its tables different types.
+    const TABLE_INFO: &'static [TableInfo];
+
+    /// 1 for a schema without a predecessor, one more than its predecessor's otherwise, see
+    /// `migration`. Recorded in the header of the log.
+    const VERSION: u32;
+
+    /// `Codec::NAME` of the codec the schema encodes its records with, recorded in the header of
+    /// the log.
+    const CODEC: &'static str;
+
+    /// The schema as returned by `init_read_only`, which has no way to write.
+    type ReadOnly;
+
+    fn open_log<P>(dir: P, options: &Options) -> Result<(File, PathBuf), Error>
     where
         P: AsRef<Path>,
     {
-        let mut path = PathBuf::new();
-        path.push(dir);
+        fs::create_dir_all(&dir).unwrap();
 
-        fs::create_dir_all(&path).unwrap();
+        let path = Self::log_path(&dir, options);
+        if options.log_name.is_none() {
+            migrate_legacy_log(&Self::legacy_log_path(&dir), &path)?;
+        }
+
+        Ok((open_file(&path)?, path))
+    }
+
+    /// Opens an existing log for reading, without creating anything. A log that still has its
+    /// legacy name is read where it is.
+    fn open_log_read_only<P>(dir: P, options: &Options) -> Result<(File, PathBuf), Error>
+    where
+        P: AsRef<Path>,
+    {
+        let mut path = Self::log_path(&dir, options);
+        let legacy = Self::legacy_log_path(&dir);
+        if options.log_name.is_none() && !path.exists() && legacy.exists() {
+            warn!(
+                "reading the log at its legacy path {:?}, opening it for writing will move it to {:?}",
+                legacy, path
+            );
+            path = legacy;
+        }
+
+        Ok((open_read_only(&path)?, path))
+    }
+
+    /// `Options::log_name` in `dir`, or `LOG_NAME` if it isn't set.
+    fn log_path<P>(dir: P, options: &Options) -> PathBuf
+    where
+        P: AsRef<Path>,
+    {
+        dir.as_ref()
+            .join(options.log_name.as_deref().unwrap_or(Self::LOG_NAME))
+    }
 
+    /// Where the log used to be kept, named after the full type name of the schema. That name
+    /// changes whenever the schema is moved to another module, so it's only used to find logs
+    /// written by older versions.
+    fn legacy_log_path<P>(dir: P) -> PathBuf
+    where
+        P: AsRef<Path>,
+    {
         let schema_name = std::any::type_name::<InMemory>().replace(':', "_");
 
-        path.push(schema_name);
+        dir.as_ref().join(schema_name)
+    }
 
-        Ok((open_file(&path)?, path))
+    /// Parses a whole file of the log held in memory, see `stream_records`, collecting its
+    /// entries.
+    fn parse_records(
+        buffer: &[u8],
+        policy: &RecoveryPolicy,
+        cipher: Option<&dyn Cipher>,
+        compressor: Option<&dyn Compressor>,
+    ) -> Result<(Vec<OnDisk>, RecoveryReport), Error> {
+        let mut entries = vec![];
+        let report = Self::stream_records(buffer, policy, cipher, compressor, 1, |entry| {
+            entries.push(entry)
+        })?;
+
+        Ok((entries, report))
     }
 
-    fn parse_log(file: &mut File) -> Result<(Vec<OnDisk>, bool), Error> {
-        let mut buffer: Vec<u8> = Vec::new();
-        file
-            .read_to_end(&mut buffer)
-            .map_err(|err| Error::OsError(format!("After having opened the db file successfully, we were unable to read it into a buffer: {}", err), err))?;
+    /// Parses every complete record of a file of the log read from `source`, a record at a time,
+    /// after checking its header against `VERSION` and `TABLE_INFO`, and hands every entry to
+    /// `apply` as soon as it's decoded. The returned report records where the last complete
+    /// record ends, anything after it is a torn write, and which damaged records were skipped if
+    /// `policy.salvage` is set. `cipher` is only used if the header says the log is sealed,
+    /// `compressor` if it names that compressor.
+    fn stream_records<R, F>(
+        source: R,
+        policy: &RecoveryPolicy,
+        cipher: Option<&dyn Cipher>,
+        compressor: Option<&dyn Compressor>,
+        threads: usize,
+        apply: F,
+    ) -> Result<RecoveryReport, Error>
+    where
+        R: Read,
+        F: FnMut(OnDisk),
+    {
+        let mut reader = FrameReader::new(source);
+        let parsed = read_header(&mut reader)?;
+        let buffer = reader.rest();
+        let mut report = RecoveryReport {
+            format_version: FORMAT_VERSION,
+            schema_version: Self::VERSION,
+            ..Default::default()
+        };
+        if !buffer.is_empty() {
+            report.schema_version = migration::schema_version(buffer).unwrap_or(Self::VERSION);
+        }
+        if report.schema_version != Self::VERSION {
+            return Err(Error::SchemaMismatch(format!(
+                "The log was written by version {} of the schema, but it's being opened by {}, \
+                which is version {}.",
+                report.schema_version,
+                std::any::type_name::<InMemory>(),
+                Self::VERSION
+            )));
+        }
+
+        let empty = buffer.is_empty();
+        let mut upcasts = vec![];
+        let mut decompressor: &dyn Compressor = &Lz;
+        let header_len = match parsed {
+            Parsed::Header { header, end } => {
+                check_codec::<InMemory>(&header.codec, Self::CODEC)?;
+                if header.encrypted && cipher.is_none() {
+                    return Err(Error::Decryption(
+                        "The log is sealed, open the db with the `Cipher` it was sealed with."
+                            .to_string(),
+                    ));
+                }
+                report.encrypted = header.encrypted;
+                decompressor = compression::resolve(header.compressor.as_deref(), compressor)?;
+                report.compressor = header.compressor;
+                upcasts = check_tables::<InMemory>(&header.tables, Self::TABLE_INFO)?;
+                for (id, _) in &upcasts {
+                    let table = Self::TABLE_INFO.iter().find(|table| table.id == *id);
+                    report
+                        .upcast_tables
+                        .extend(table.map(|table| table.name.to_string()));
+                }
+                end
+            }
+            Parsed::Unsupported { version } => {
+                return Err(Error::UnsupportedFormat(format!(
+                    "The log is in format version {}, this version of hmdb reads up to format \
+                    version {}.",
+                    version, FORMAT_VERSION
+                )))
+            }
+            Parsed::Missing if buffer.is_empty() => 0,
+            Parsed::Missing => {
+                check_codec::<InMemory>(Bincode::NAME, Self::CODEC)?;
+                report.format_version = 0;
+                0
+            }
+            // A crash while the log was being created, there are no records yet
+            Parsed::Torn => {
+                report.bytes_read = reader.bytes_read();
+                return Ok(report);
+            }
+            Parsed::Corrupt if policy.salvage => {
+                reader.resync().map_err(read_error)?;
+                let next = reader.offset();
+                warn!("skipping the damaged header, bytes 0..{} of the log", next);
+                report.skipped.push(0..next);
+                // Already past it
+                0
+            }
+            Parsed::Corrupt => {
+                return Err(Error::CorruptLog(
+                    "The header of the log is damaged, so the schema that wrote it can't be \
+                    checked. Open the db with `RecoveryPolicy::salvage` to skip it."
+                        .to_string(),
+                ))
+            }
+        };
+
+        // Anyone can write a log that isn't sealed, so one isn't read in place of a sealed log
+        if cipher.is_some() && !report.encrypted && !empty && !policy.seal_plaintext {
+            return Err(Error::Decryption(
+                "The log isn't sealed, but the db is opened with a `Cipher`. Open it with \
+                `OpenOptions::seal_plaintext` to seal it."
+                    .to_string(),
+            ));
+        }
 
-        if buffer.is_empty() {
-            return Ok((vec![], false));
+        let format = report.format_version;
+        let cipher = cipher.filter(|_| report.encrypted);
+        reader.advance(header_len);
+        if format == 0 {
+            reader.read_legacy();
+        }
+        let decoder = Decoder {
+            policy,
+            cipher,
+            decompressor,
+            decode: |data: &[u8]| Self::decode(data, format, &upcasts),
+        };
+        match threads > 1 {
+            true => parse_frames_on_threads(&mut reader, &decoder, report, threads, apply),
+            false => parse_frames(&mut reader, &decoder, report, apply),
         }
+    }
+
+    /// Parses records appended to a log after its header was read, which are always in the
+    /// current format, sealed if `cipher` is given, and compressed by `compressor`, or `Lz` if
+    /// it isn't given.
+    fn parse_appended(
+        buffer: &[u8],
+        policy: &RecoveryPolicy,
+        cipher: Option<&dyn Cipher>,
+        compressor: Option<&dyn Compressor>,
+    ) -> Result<(Vec<OnDisk>, RecoveryReport), Error> {
+        let report = RecoveryReport {
+            format_version: FORMAT_VERSION,
+            schema_version: Self::VERSION,
+            encrypted: cipher.is_some(),
+            compressor: compressor.map(|compressor| compressor.name().to_string()),
+            ..Default::default()
+        };
+        let decoder = Decoder {
+            policy,
+            cipher,
+            decompressor: compressor.unwrap_or(&Lz),
+            decode: |data: &[u8]| Self::decode(data, FORMAT_VERSION, &[]),
+        };
+        let mut entries = vec![];
+        let report = parse_frames(&mut FrameReader::new(buffer), &decoder, report, |entry| {
+            entries.push(entry)
+        })?;
 
-        let mut log_entries = vec![];
+        Ok((entries, report))
+    }
 
-        let mut index = 0;
-        while index < buffer.len() {
-            if buffer.len() < index + 4 {
-                return Ok((log_entries, true));
+    /// Parses every file of a log, oldest first, see `LogFiles`, handing every entry to `apply`
+    /// as soon as it's decoded. The report is the live segment's, except that it lists what was
+    /// skipped in any file, the oldest format any of them is in, and what was read from all of
+    /// them. With more than one of `threads`, records are decoded on that many worker threads,
+    /// and handed to `apply` in the order they were logged in all the same, see
+    /// `Options::replay_threads`.
+    fn parse_files<F>(
+        log: &LogFiles,
+        policy: &RecoveryPolicy,
+        cipher: Option<&dyn Cipher>,
+        compressor: Option<&dyn Compressor>,
+        threads: usize,
+        mut apply: F,
+    ) -> Result<RecoveryReport, Error>
+    where
+        F: FnMut(OnDisk),
+    {
+        let mut merged = RecoveryReport::default();
+        let mut encrypted = true;
+        let mut format_version = FORMAT_VERSION;
+        let mut skipped = vec![];
+        let mut upcast_tables: Vec<String> = vec![];
+        let mut valid_bytes = 0;
+        let mut offset = 0;
+        let mut records = 0;
+        let total_bytes = log.sealed.iter().map(LogSource::len).sum::<u64>()
+            + match log.obsolete_live {
+                true => 0,
+                false => log.live.len(),
+            };
+        for (index, file) in log.sealed.iter().chain([&log.live]).enumerate() {
+            let sealed = index < log.sealed.len();
+            let policy = &RecoveryPolicy {
+                progress: policy.progress.as_ref().map(|progress| {
+                    progress.after(ReplayProgress {
+                        bytes_read: offset,
+                        total_bytes,
+                        records,
+                    })
+                }),
+                ..policy.clone()
+            };
+            let (len, mut report) = match sealed || !log.obsolete_live {
+                true => {
+                    let report = Self::stream_records(
+                        file.reader()?,
+                        policy,
+                        cipher,
+                        compressor,
+                        threads,
+                        &mut apply,
+                    )?;
+                    (file.len(), report)
+                }
+                false => {
+                    let empty = io::empty();
+                    let report =
+                        Self::stream_records(empty, policy, cipher, compressor, 1, &mut apply)?;
+                    (0, report)
+                }
+            };
+            if !sealed {
+                valid_bytes = match log.obsolete_live {
+                    true => log.live.len(),
+                    false => report.valid_bytes,
+                };
+            } else if report.valid_bytes < len {
+                // Sealed files are fsynced before they're renamed into place, and never written again
+                if !policy.salvage {
+                    return Err(Error::CorruptLog(format!(
+                        "A sealed file of the log, the {} oldest, ends {} bytes early. Open the db \
+                        with `RecoveryPolicy::salvage` to skip the rest of it.",
+                        index + 1,
+                        len - report.valid_bytes
+                    )));
+                }
+                report.skipped.push(report.valid_bytes..len);
             }
 
-            let size = u32::from_be_bytes(
-                buffer[index..index + 4]
-                    .try_into()
-                    .expect("slice with incorrect length"),
+            format_version = format_version.min(report.format_version);
+            skipped.extend(
+                report
+                    .skipped
+                    .drain(..)
+                    .map(|range| range.start + offset..range.end + offset),
             );
+            for table in report.upcast_tables.drain(..) {
+                if !upcast_tables.contains(&table) {
+                    upcast_tables.push(table);
+                }
+            }
+            offset += len;
+            records += report.records;
+            if len > 0 {
+                encrypted &= report.encrypted;
+            }
+            // An empty live segment doesn't know how the log is sealed or compressed
+            if len > 0 || index == 0 {
+                merged = report;
+            }
+        }
+
+        merged.valid_bytes = valid_bytes;
+        // Every file is rewritten sealed unless they all are
+        merged.encrypted &= encrypted;
+        merged.format_version = format_version;
+        merged.skipped = skipped;
+        merged.upcast_tables = upcast_tables;
+        merged.records = records;
+        merged.bytes_read = offset;
+        Ok(merged)
+    }
+
+    /// Decodes the payload of a record written in `format`. `upcasts` pairs the id of every table
+    /// whose records hold an earlier value type with that type's index in `TableInfo::history`.
+    fn decode(
+        data: &[u8],
+        _format: u32,
+        _upcasts: &[(u32, usize)],
+    ) -> Result<LogItems<OnDisk>, bincode::Error> {
+        bincode::deserialize(data)
+    }
+
+    /// Cuts the log back to `report.valid_bytes`, the end of the last complete record, so that
+    /// new records are not appended after the remains of a torn write.
+    fn truncate_torn_tail<P: AsRef<Path>>(
+        file: &mut File,
+        path: P,
+        report: &mut RecoveryReport,
+        policy: &RecoveryPolicy,
+    ) -> Result<(), Error> {
+        let valid_len = report.valid_bytes;
+        let file_len = file
+            .metadata()
+            .map_err(|err| {
+                Error::OsError(
+                    format!("Failed to read the length of the log: {}", err),
+                    err,
+                )
+            })?
+            .len();
+
+        report.truncated_bytes = file_len.saturating_sub(valid_len);
+        if report.truncated_bytes == 0 {
+            return Ok(());
+        }
+        warn!(
+            "log {:?} ends with a torn write, truncating {} bytes",
+            path.as_ref(),
+            report.truncated_bytes
+        );
+
+        if policy.preserve_torn_tail {
+            let mut tail = vec![];
+            file.seek(SeekFrom::Start(valid_len))
+                .and_then(|_| file.read_to_end(&mut tail))
+                .map_err(|err| {
+                    Error::OsError(
+                        format!("Failed to read the torn tail of the log: {}", err),
+                        err,
+                    )
+                })?;
+
+            let millis = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis();
+            let tail_path = with_suffix(path.as_ref(), &format!("torn-{}", millis));
+            fs::write(&tail_path, tail).map_err(|err| {
+                Error::OsError(
+                    format!(
+                        "Failed to save the torn tail of the log to {:?}: {}",
+                        tail_path, err
+                    ),
+                    err,
+                )
+            })?;
+
+            report.torn_tail = Some(tail_path);
+        }
+
+        file.set_len(valid_len)
+            .and_then(|_| file.sync_data())
+            .map_err(|err| {
+                Error::OsError(
+                    format!("Failed to truncate the torn tail of the log: {}", err),
+                    err,
+                )
+            })?;
+
+        Ok(())
+    }
+
+    fn incomplete_write(&self) -> bool;
+
+    fn recovery_report(&self) -> &RecoveryReport;
+
+    /// Picks up writes that other processes made to the log since this process last read it.
+    /// Only does anything for a db opened with `Locking::Shared`, where writes do this on their
+    /// own, but reads don't.
+    fn refresh(&self) -> Result<(), Error>;
+
+    fn init<P: AsRef<Path>>(path: P) -> Result<InMemory, Error> {
+        Self::init_with(path, Options::default())
+    }
+
+    fn init_with_recovery<P: AsRef<Path>>(
+        path: P,
+        recovery: RecoveryPolicy,
+    ) -> Result<InMemory, Error> {
+        Self::init_with(
+            path,
+            Options {
+                recovery,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Opens the db with `options`, or `Error::InvalidOptions` if they don't pass
+    /// `Options::validate`.
+    fn init_with<P: AsRef<Path>>(path: P, options: Options) -> Result<InMemory, Error>;
+
+    /// Opens an existing db without creating, writing or locking anything, so it can be
+    /// inspected while another process has it open. Fails if there's no db at `path`. A torn
+    /// write at the end of the log is skipped rather than truncated.
+    fn init_read_only<P: AsRef<Path>>(path: P) -> Result<Self::ReadOnly, Error> {
+        Self::init_read_only_with(path, Options::default())
+    }
+
+    /// Like `init_read_only`, only `options.recovery`, `options.cipher`, `options.compression`
+    /// and `options.log_name` apply.
+    fn init_read_only_with<P: AsRef<Path>>(
+        path: P,
+        options: Options,
+    ) -> Result<Self::ReadOnly, Error>;
+
+    /// Starts building the options to open the db with.
+    fn options() -> options::OpenOptions<InMemory> {
+        options::OpenOptions::new()
+    }
+}
+
+/// How the records of a log are turned back into entries, see `Reader::stream_records`.
+struct Decoder<'a, D> {
+    policy: &'a RecoveryPolicy,
+    /// Opens the records, if the log is sealed.
+    cipher: Option<&'a dyn Cipher>,
+    decompressor: &'a dyn Compressor,
+    decode: D,
+}
 
-            index += 4;
+/// A record that's waiting to be decoded on a worker thread, see `parse_frames_on_threads`.
+struct Pending {
+    index: u64,
+    end: usize,
+    payload: Vec<u8>,
+    compressed: bool,
+}
+
+/// What a worker thread made of a `Pending` record, see `Decoder::open`.
+type Decoded<OnDisk> = (
+    Pending,
+    Result<Result<LogItems<OnDisk>, bincode::Error>, Error>,
+);
+
+/// Records sent to a worker thread at a time, by their size in bytes.
+const DECODE_BATCH: usize = 1 << 20;
+
+/// How many bytes of entries a record of a snapshot holds, about, see `Writer::write_chunked`.
+const SNAPSHOT_CHUNK: u64 = 64 << 20;
 
-            // This cast should be fine on both 32 bit and 64 bit systems, on a less-than 32 bit
-            // system, with a size value larger than usize::Max this will overflow or panic
-            if buffer.len() < index + (size as usize) {
-                return Ok((log_entries, true));
+impl<D, OnDisk> Decoder<'_, D>
+where
+    D: Fn(&[u8]) -> Result<LogItems<OnDisk>, bincode::Error>,
+{
+    /// Opens, decompresses and decodes the payload of the record at `index`. The outer error
+    /// means the log can't be read, the inner one that the record can't be decoded, which
+    /// `RecoveryPolicy::salvage` skips.
+    fn open(
+        &self,
+        data: &[u8],
+        compressed: bool,
+        index: u64,
+    ) -> Result<Result<LogItems<OnDisk>, bincode::Error>, Error> {
+        // A record that fails to open passed its checksum, so it isn't damaged, and skipping it
+        // with `salvage` would skip every record sealed with another key
+        let opened;
+        let data = match self.cipher {
+            Some(cipher) => {
+                opened = cipher.open(data).map_err(|err| {
+                    Error::Decryption(format!(
+                        "The record at offset {} of the log couldn't be opened, it was sealed \
+                        with another key or tampered with: {}",
+                        index, err
+                    ))
+                })?;
+                opened.as_slice()
             }
+            None => data,
+        };
+        let decompressed;
+        let data = if compressed {
+            decompressed = self.decompressor.decompress(data).map_err(|err| {
+                Error::Decompression(format!(
+                    "The record at offset {} of the log couldn't be decompressed by `{}`: {}",
+                    index,
+                    self.decompressor.name(),
+                    err
+                ))
+            })?;
+            decompressed.as_slice()
+        } else {
+            data
+        };
 
-            let data = &buffer[index..index + (size as usize)];
+        Ok((self.decode)(data))
+    }
 
-            let parsed: LogItems<OnDisk> = bincode::deserialize(data).map_err(|err| Error::LogParseError(format!(
-                "While parsing the log we were looking for {} bytes for the next entry, we found \
-                that many bytes, but they failed to deserialize into the type {}. This could \
-                indicate a Schema Data mismatch, or a corrupted log. There are {} bytes left in the \
-                log after this entry. Bincode error: {}",
-                size,
+    /// Hands the entries of a decoded record to `apply`, or, if it failed to decode, skips it
+    /// with `salvage`. `skip` moves `reader` past a record that can't be decoded, and returns
+    /// where it ends up.
+    fn apply<A, S>(
+        &self,
+        decoded: Result<LogItems<OnDisk>, bincode::Error>,
+        len: usize,
+        index: u64,
+        report: &mut RecoveryReport,
+        apply: &mut A,
+        skip: S,
+    ) -> Result<(), Error>
+    where
+        A: FnMut(OnDisk),
+        S: FnOnce() -> Result<u64, Error>,
+    {
+        match decoded {
+            Ok(LogItems::Single(entry)) => {
+                report.records += 1;
+                apply(entry)
+            }
+            Ok(LogItems::Batch(entries)) => {
+                report.records += 1;
+                entries.into_iter().for_each(apply)
+            }
+            Err(err) if self.policy.salvage => {
+                let next = skip()?;
+                warn!(
+                    "skipping undecodable bytes {}..{} of the log: {}",
+                    index, next, err
+                );
+                report.skipped.push(index..next);
+            }
+            Err(err) => {
+                return Err(Error::LogParseError(
+                    format!(
+                "While parsing the log we were looking for {} bytes for the next entry, at offset \
+                {}, we found that many bytes, but they failed to deserialize into the type {}. \
+                This could indicate a Schema Data mismatch, or a corrupted log. Bincode error: {}",
+                len,
+                index,
                 std::any::type_name::<OnDisk>(),
-                (buffer.len() as i64) - ((index + (size as usize)) as i64),
                 err
-            ), err))?;
-            match parsed {
-                LogItems::Single(entry) => log_entries.push(entry),
-                LogItems::Batch(entries) => log_entries.extend(entries),
+            ),
+                    err,
+                ))
             }
+        }
+
+        Ok(())
+    }
 
-            index += size as usize;
+    /// Hands how far replaying has got, up to `offset`, to `RecoveryPolicy::progress`, if it was
+    /// last handed more than `PROGRESS_BYTES` ago, at `reported`, or `end` is set.
+    fn progress(&self, offset: u64, report: &RecoveryReport, reported: &mut u64, end: bool) {
+        if let Some(progress) = &self.policy.progress {
+            if end || offset >= *reported + PROGRESS_BYTES {
+                progress.report(offset, report.records);
+                *reported = offset;
+            }
         }
+    }
+}
 
-        Ok((log_entries, false))
+/// Skips the damaged bytes at `index`, where `reader` is, up to the next readable record, with
+/// `salvage`. `false` if nothing readable follows, which is what a crash during a write leaves
+/// behind. In a `sealed` log that's all a crash can leave, a record cut short by the end of the
+/// log, anything else was tampered with, and isn't skipped.
+fn skip_damaged<R: Read>(
+    reader: &mut FrameReader<R>,
+    index: u64,
+    torn: bool,
+    sealed: bool,
+    policy: &RecoveryPolicy,
+    report: &mut RecoveryReport,
+) -> Result<bool, Error> {
+    let tampered = || {
+        Error::Decryption(format!(
+            "The record at offset {} of the sealed log doesn't match its checksum, it was \
+            tampered with.",
+            index
+        ))
+    };
+    if sealed && !torn {
+        return Err(tampered());
+    }
+    if !reader.resync().map_err(read_error)? {
+        return Ok(false);
     }
 
-    fn incomplete_write(&self) -> bool;
+    let next = reader.offset();
+    if sealed {
+        return Err(tampered());
+    }
+    if !policy.salvage {
+        return Err(Error::CorruptLog(format!(
+            "The record at offset {} of the log is damaged, but readable records follow it at \
+            offset {}. Open the db with `RecoveryPolicy::salvage` to skip the damaged bytes.",
+            index, next
+        )));
+    }
+    warn!("skipping damaged bytes {}..{} of the log", index, next);
+    report.skipped.push(index..next);
 
-    fn init<P: AsRef<Path>>(path: P) -> Result<InMemory, Error>;
+    Ok(true)
 }
 
-pub trait LogCompacter {
-    fn compact_log(&self) -> Result<(), Error>;
+/// Parses the records `reader` reads, handing every entry to `apply`, see
+/// `Reader::stream_records`.
+fn parse_frames<OnDisk, R, D, A>(
+    reader: &mut FrameReader<R>,
+    decoder: &Decoder<D>,
+    mut report: RecoveryReport,
+    mut apply: A,
+) -> Result<RecoveryReport, Error>
+where
+    R: Read,
+    D: Fn(&[u8]) -> Result<LogItems<OnDisk>, bincode::Error>,
+    A: FnMut(OnDisk),
+{
+    let mut reported = 0;
+    let valid_bytes = loop {
+        let index = reader.offset();
+        let (data, compressed, end, verified) = match reader.frame().map_err(read_error)? {
+            None => break index,
+            Some(Frame::Record {
+                payload,
+                compressed,
+                end,
+            }) => (payload, compressed, end, true),
+            Some(Frame::Legacy { payload, end }) => (payload, false, end, false),
+            Some(frame @ (Frame::Torn | Frame::Corrupt)) => {
+                let torn = matches!(frame, Frame::Torn);
+                let sealed = decoder.cipher.is_some();
+                match skip_damaged(reader, index, torn, sealed, decoder.policy, &mut report)? {
+                    true => continue,
+                    false => break index,
+                }
+            }
+        };
+        let len = data.len();
 
-    fn start_background_compacter(
-        &self,
-        time_between_compacts: Duration,
-    ) -> Result<JoinHandle<Error>, Error>;
+        let decoded = decoder.open(data, compressed, index)?;
+        let mut skipped = false;
+        decoder.apply(decoded, len, index, &mut report, &mut apply, || {
+            skipped = true;
+            // A legacy record can't be verified, so its size can't be trusted either
+            match verified {
+                true => reader.advance(end),
+                false => {
+                    reader.resync().map_err(read_error)?;
+                }
+            }
+            Ok(reader.offset())
+        })?;
+        if !skipped {
+            reader.advance(end);
+        }
+        decoder.progress(reader.offset(), &report, &mut reported, false);
+    };
+
+    report.valid_bytes = valid_bytes;
+    report.bytes_read = reader.bytes_read();
+    decoder.progress(report.bytes_read, &report, &mut reported, true);
+    Ok(report)
 }
 
-#[derive(Clone, Debug)]
-pub struct Writer {
-    file: Arc<Mutex<File>>,
-    path: Arc<PathBuf>,
+/// Like `parse_frames`, but the records are decoded on `threads` worker threads, while this one
+/// reads them, and applies them in the order they were read. Anything that isn't a verified
+/// record waits for the records before it, and is dealt with on this thread.
+fn parse_frames_on_threads<OnDisk, R, D, A>(
+    reader: &mut FrameReader<R>,
+    decoder: &Decoder<D>,
+    mut report: RecoveryReport,
+    threads: usize,
+    mut apply: A,
+) -> Result<RecoveryReport, Error>
+where
+    OnDisk: Send,
+    R: Read,
+    D: Fn(&[u8]) -> Result<LogItems<OnDisk>, bincode::Error> + Sync,
+    A: FnMut(OnDisk),
+{
+    let (jobs, queue) = mpsc::sync_channel::<(u64, Vec<Pending>)>(threads);
+    let queue = Mutex::new(queue);
+    let (done, results) = mpsc::channel::<(u64, Vec<Decoded<OnDisk>>)>();
+    thread::scope(|scope| {
+        // Dropped when this returns, early or not, which stops the workers before they're joined
+        let jobs = jobs;
+        for _ in 0..threads {
+            let queue = &queue;
+            let done = done.clone();
+            scope.spawn(move || loop {
+                let job = match queue.lock() {
+                    Ok(queue) => queue.recv(),
+                    Err(_) => return,
+                };
+                let Ok((batch, records)) = job else { return };
+                let decoded = records
+                    .into_iter()
+                    .map(|record| {
+                        let opened = decoder.open(&record.payload, record.compressed, record.index);
+                        (record, opened)
+                    })
+                    .collect();
+                if done.send((batch, decoded)).is_err() {
+                    return;
+                }
+            });
+        }
+        drop(done);
+
+        let mut sent = 0;
+        let mut applied = 0;
+        let mut reported = 0;
+        let mut finished = BTreeMap::new();
+        // Applies the decoded batches, in order, until no more than `pending` are left
+        let mut apply_until = |pending: u64,
+                               sent: u64,
+                               report: &mut RecoveryReport,
+                               apply: &mut A| {
+            while sent - applied > pending {
+                let decoded = match finished.remove(&applied) {
+                    Some(decoded) => decoded,
+                    None => {
+                        let (batch, decoded) = results.recv().map_err(|_| {
+                            Error::LockError("A thread decoding the log panicked.".to_string())
+                        })?;
+                        finished.insert(batch, decoded);
+                        continue;
+                    }
+                };
+                for (record, opened) in decoded {
+                    let Pending { index, end, .. } = record;
+                    decoder.apply(opened?, record.payload.len(), index, report, apply, || {
+                        Ok(index + end as u64)
+                    })?;
+                    decoder.progress(index + end as u64, report, &mut reported, false);
+                }
+                applied += 1;
+            }
+            Ok::<_, Error>(())
+        };
+
+        let mut batch = vec![];
+        let mut batch_len = 0;
+        let valid_bytes = loop {
+            let index = reader.offset();
+            let frame = reader.frame().map_err(read_error)?;
+            if let Some(Frame::Record {
+                payload,
+                compressed,
+                end,
+            }) = frame
+            {
+                batch_len += payload.len();
+                batch.push(Pending {
+                    index,
+                    end,
+                    payload: payload.to_vec(),
+                    compressed,
+                });
+                reader.advance(end);
+                if batch_len >= DECODE_BATCH {
+                    jobs.send((sent, std::mem::take(&mut batch))).map_err(|_| {
+                        Error::LockError("The threads decoding the log stopped.".to_string())
+                    })?;
+                    sent += 1;
+                    batch_len = 0;
+                    apply_until(threads as u64 * 2, sent, &mut report, &mut apply)?;
+                }
+                continue;
+            }
+
+            // Everything before it has to be applied first
+            if !batch.is_empty() {
+                jobs.send((sent, std::mem::take(&mut batch))).map_err(|_| {
+                    Error::LockError("The threads decoding the log stopped.".to_string())
+                })?;
+                sent += 1;
+                batch_len = 0;
+            }
+            apply_until(0, sent, &mut report, &mut apply)?;
+            match reader.frame().map_err(read_error)? {
+                None => break index,
+                Some(Frame::Legacy { payload, end }) => {
+                    let len = payload.len();
+                    let decoded = decoder.open(payload, false, index)?;
+                    let mut skipped = false;
+                    decoder.apply(decoded, len, index, &mut report, &mut apply, || {
+                        skipped = true;
+                        reader.resync().map_err(read_error)?;
+                        Ok(reader.offset())
+                    })?;
+                    if !skipped {
+                        reader.advance(end);
+                    }
+                }
+                Some(frame) => {
+                    let torn = matches!(frame, Frame::Torn);
+                    let sealed = decoder.cipher.is_some();
+                    if !skip_damaged(reader, index, torn, sealed, decoder.policy, &mut report)? {
+                        break index;
+                    }
+                }
+            }
+        };
+
+        report.valid_bytes = valid_bytes;
+        report.bytes_read = reader.bytes_read();
+        decoder.progress(report.bytes_read, &report, &mut reported, true);
+        Ok(report)
+    })
 }
 
-impl Writer {
-    pub fn init<P: AsRef<Path>>(file: File, path: P) -> Self {
-        let file = Arc::new(Mutex::new(file));
-        let path = Arc::new(path.as_ref().to_path_buf());
+/// Reads until `reader` holds the whole header of the log, or the log ends, and parses it.
+fn read_header<R: Read>(reader: &mut FrameReader<R>) -> Result<Parsed, Error> {
+    let mut len = 4096;
+    loop {
+        reader.fill(len).map_err(read_error)?;
+        match header::read(reader.rest()) {
+            Parsed::Torn if !reader.at_end() => len *= 2,
+            parsed => return Ok(parsed),
+        }
+    }
+}
+
+fn read_error(err: io::Error) -> Error {
+    Error::OsError(format!("Failed to read the log: {}", err), err)
+}
 
-        Self { file, path }
+/// Logs written before schemas chose their codec were written with bincode.
+fn check_codec<InMemory>(logged: &str, codec: &str) -> Result<(), Error> {
+    if logged == codec {
+        return Ok(());
     }
 
-    pub fn append<S: Serialize>(&self, data: &S) -> Result<(), Error> {
-        let mut file = self.file
-            .lock()
-            .map_err(|err| Error::LockError(format!("Writer lock poisoned, this suggest an internal, unexpected, database error. Error: {}", err)))?;
+    Err(Error::SchemaMismatch(format!(
+        "The log was written with the codec `{}`, but it's being opened by {} which uses the \
+        codec `{}`. Change the codec of a schema by migrating to a new version of it.",
+        logged,
+        std::any::type_name::<InMemory>(),
+        codec
+    )))
+}
 
-        Self::write_to_log(&mut *file, &LogItems::Single(data))
+/// Checks the tables recorded in a log's header against the tables of the schema opening it.
+/// Tables that only one of them has are fine, the records of a removed table are ignored, but
+/// a table has to keep its types, or list the old ones in its history, and a table that kept its
+/// name has to keep its id. Returns the tables to upcast, see `Reader::decode`.
+fn check_tables<InMemory>(
+    logged: &[header::Table],
+    tables: &[TableInfo],
+) -> Result<Vec<(u32, usize)>, Error> {
+    let schema = std::any::type_name::<InMemory>();
+    let types = header::fingerprint;
+    let mut upcasts = vec![];
+    for old in logged {
+        match tables.iter().find(|table| table.id == old.id) {
+            Some(table) if types(table.types) == old.types => {}
+            Some(table) => match table
+                .history
+                .iter()
+                .position(|earlier| types(earlier) == old.types)
+            {
+                Some(index) => upcasts.push((table.id, index)),
+                None => {
+                    return Err(Error::SchemaMismatch(format!(
+                        "The log was written by a schema whose table `{}` (id {}) has the types \
+                        `{}`, but it's being opened by {} whose table `{}` has the types `{}`, \
+                        and doesn't list the old value type as an earlier one.",
+                        old.name,
+                        old.id,
+                        old.types,
+                        schema,
+                        table.name,
+                        types(table.types)
+                    )))
+                }
+            },
+            None => match tables.iter().find(|table| table.name == old.name) {
+                Some(table) => {
+                    return Err(Error::SchemaMismatch(format!(
+                        "The log was written by a schema whose table `{}` has the id {}, but \
+                        it's being opened by {} which gives it the id {}. Give it back its old \
+                        id with `#[id = {}]`.",
+                        old.name, old.id, schema, table.id, old.id
+                    )))
+                }
+                None => warn!(
+                    "the log has records of the table `{}` (id {}), which {} doesn't have. \
+                    They're ignored, and dropped by the next compaction.",
+                    old.name, old.id, schema
+                ),
+            },
+        }
     }
 
-    pub fn append_all<S: Serialize>(&self, data: Vec<S>) -> Result<(), Error> {
-        let mut file = self.file
-            .lock()
-            .map_err(|err| Error::LockError(format!("Writer lock poisoned, this suggest an internal, unexpected, database error. Error: {}", err)))?;
+    Ok(upcasts)
+}
 
-        Self::write_to_log(&mut *file, &LogItems::Batch(data))
+/// Sealed segments are named `SchemaName.wal.<generation>`.
+const SEGMENT: &str = "wal";
+/// Snapshots are named `SchemaName.snapshot.<generation>`.
+const SNAPSHOT: &str = "snapshot";
+/// A new live segment is written to `SchemaName.live.<generation>.tmp` before it's renamed.
+const LIVE: &str = "live";
+/// The files of the log are listed in `SchemaName.manifest`.
+const MANIFEST: &str = "manifest";
+
+/// The files a log is kept in, next to each other in the db's directory:
+///
+/// + the snapshot, `SchemaName.snapshot.<generation>`, every entry of every table as of the end
+///   of the segment of its generation,
+/// + the sealed segments, `SchemaName.wal.<generation>`, which compactions stopped appending to,
+/// + and the live segment, `SchemaName`, which records are appended to.
+///
+/// Every file records its generation in its header. The snapshot, then the segments after it,
+/// then the live segment, replayed in order, restore the tables. A compaction seals the live
+/// segment and starts a new one, which is all writers wait for. It then replays the previous
+/// snapshot and the sealed segments into the snapshot of the sealed segment, and removes the
+/// files that snapshot covers, see `Sealed::compact`. Files a crash left behind that a
+/// snapshot covers are skipped. With `Options::segment_bytes`, a writer also seals the live
+/// segment whenever it grows past the limit.
+///
+/// The writer lists these files, by name and in the order they're replayed in, one per line, in
+/// `SchemaName.manifest`, which it replaces whenever they change. A file is only removed once the
+/// manifest no longer lists it, and only the live segment changes while it's listed, so backups
+/// can copy what the manifest lists, and archive every sealed file once.
+#[derive(Debug)]
+pub struct LogFiles {
+    /// The snapshot, if there is one, and the sealed segments after it, oldest first.
+    pub sealed: Vec<LogSource>,
+    pub live: LogSource,
+    /// Whether the snapshot covers the live segment, which happens when a rewrite is interrupted
+    /// before it replaces it. Only a reader sees one, a writer empties it.
+    pub obsolete_live: bool,
+    /// The generation of the live segment.
+    pub generation: u64,
+}
+
+impl LogFiles {
+    /// The oldest file of the log, which was written by the oldest version of the schema, see
+    /// `migration`.
+    pub fn oldest(&self) -> &LogSource {
+        self.sealed.first().unwrap_or(&self.live)
     }
+}
 
-    pub fn compact_log<S: Serialize>(&self, data: Vec<S>) -> Result<(), Error> {
-        let new_db_path = self.path.with_extension(".log_compaction");
-        let mut new_db = open_file(&new_db_path)?;
+/// A file of the log, see `LogFiles`, opened to be replayed a record at a time. Only its header
+/// is read up front.
+#[derive(Debug)]
+pub struct LogSource {
+    file: File,
+    len: u64,
+    head: Vec<u8>,
+}
 
-        Self::write_to_log(&mut new_db, &LogItems::Batch(data))?;
+impl LogSource {
+    /// Reads the header of `file`, of which only the first `len` bytes are replayed.
+    fn new(file: File, len: u64) -> Result<Self, Error> {
+        let mut head_len = 4096;
+        let head = loop {
+            let head = read_range(&file, 0, len.min(head_len))?;
+            match header::read(&head) {
+                Parsed::Torn if (head.len() as u64) < len => head_len *= 2,
+                _ => break head,
+            }
+        };
 
-        fs::rename(new_db_path, self.path.as_ref()).map_err(|err| {
-            Error::OsError(
-                format!(
-                    "Failed to atomically overwrite old log with compacted log, error: {:?}.",
-                    err
-                ),
-                err,
-            )
-        })?;
+        Ok(Self { file, len, head })
+    }
 
-        let mut file = self
-            .file
-            .lock()
-            .map_err(|err| Error::LockError(format!("Writer lock poisoned, this suggest an internal, unexpected, database error. Error: {}", err)))?;
+    /// The first bytes of the file, enough to hold its header, if it has one.
+    pub fn head(&self) -> &[u8] {
+        &self.head
+    }
 
-        *file = new_db;
+    pub fn len(&self) -> u64 {
+        self.len
+    }
 
-        Ok(())
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Reads the file from the start, through a `BufReader`.
+    fn reader(&self) -> Result<impl Read + '_, Error> {
+        (&self.file).seek(SeekFrom::Start(0)).map_err(read_error)?;
+        Ok(BufReader::new(&self.file).take(self.len))
+    }
+
+    fn read_all(&self) -> Result<Vec<u8>, Error> {
+        read_range(&self.file, 0, self.len)
+    }
+}
+
+/// Reads the files of the log whose live segment is `live`, opened at `path`, see `LogFiles`. A
+/// writer removes the files a snapshot covers, and empties a live segment it covers. A reader
+/// skips them, and reads again if a compaction in another process changed the files while they
+/// were read.
+#[doc(hidden)]
+pub fn read_files(mut live: File, path: &Path, writable: bool) -> Result<(File, LogFiles), Error> {
+    for _ in 0..10 {
+        if let Some(files) = try_read_files(&live, path, writable)? {
+            return Ok((live, files));
+        }
+        thread::sleep(Duration::from_millis(10));
+        live = open_live(path, writable)?;
     }
 
-    fn write_to_log<S: Serialize>(file: &mut File, data: &LogItems<S>) -> Result<(), Error> {
-        let mut data = bincode::serialize(data)
-            .map_err(|err| Error::serialize(std::any::type_name::<LogItems<S>>(), err))?;
-        let size = data.len() as u32;
+    Err(Error::CorruptLog(format!(
+        "The files of the log {:?} kept changing while they were read.",
+        path
+    )))
+}
+
+/// `None` if the files changed while they were read.
+fn try_read_files(live: &File, path: &Path, writable: bool) -> Result<Option<LogFiles>, Error> {
+    let mut files = LogFiles {
+        sealed: vec![],
+        live: LogSource::new(clone_file(live)?, file_len(live)?)?,
+        obsolete_live: false,
+        generation: 0,
+    };
+    let covered = list_numbered(path, SNAPSHOT, "")?.pop().unwrap_or(0);
+    let changed = || -> Result<bool, Error> {
+        Ok(
+            list_numbered(path, SNAPSHOT, "")?.pop().unwrap_or(0) != covered
+                || !same_file(live, path)?,
+        )
+    };
+
+    if covered > 0 {
+        match open_if_exists(&numbered(path, SNAPSHOT, covered))? {
+            Some(snapshot) => files.sealed.push(snapshot),
+            None => return Ok(None),
+        }
+    }
+    let mut generation = covered + 1;
+    for segment in list_numbered(path, SEGMENT, "")? {
+        if segment <= covered {
+            continue;
+        }
+        if segment != generation {
+            if changed()? {
+                return Ok(None);
+            }
+            return Err(Error::CorruptLog(format!(
+                "The segment {:?} of the log is missing, the records that were in it are lost.",
+                numbered(path, SEGMENT, generation)
+            )));
+        }
+        match open_if_exists(&numbered(path, SEGMENT, segment))? {
+            Some(segment) => files.sealed.push(segment),
+            None => return Ok(None),
+        }
+        generation += 1;
+    }
 
-        let mut to_write = size.to_be_bytes().to_vec();
-        to_write.append(&mut data);
-        file.write_all(&to_write).map_err(|err| {
+    // A live segment without a header yet is the one the files so far are missing
+    let live_generation = header::generation(files.live.head()).unwrap_or(generation);
+    files.generation = live_generation;
+    if live_generation <= covered && writable {
+        warn!("emptying the live segment {:?}, a snapshot covers it", path);
+        live.set_len(0).map_err(|err| {
             Error::OsError(
-                format!(
-                    "Failed to append {} bytes to the log, error: {}",
-                    to_write.len(),
-                    err
-                ),
+                format!("Failed to empty the obsolete live segment: {}", err),
                 err,
             )
         })?;
+        files.live = LogSource::new(clone_file(live)?, 0)?;
+        files.generation = generation;
+    } else if live_generation <= covered {
+        files.obsolete_live = true;
+    } else if live_generation != generation {
+        if changed()? {
+            return Ok(None);
+        }
+        return Err(Error::CorruptLog(format!(
+            "The live segment of the log {:?} is of generation {}, but the files before it end \
+            with generation {}.",
+            path,
+            live_generation,
+            generation - 1
+        )));
+    }
+    if !writable && changed()? {
+        return Ok(None);
+    }
 
-        Ok(())
+    if writable {
+        write_manifest(path)?;
+        remove_covered(path, covered)?;
+        for live in list_numbered(path, LIVE, ".tmp")? {
+            remove_if_exists(&temporary(&numbered(path, LIVE, live)))?;
+        }
+        // Where compactions used to write the compacted log
+        remove_if_exists(&path.with_extension(".log_compaction"))?;
     }
+
+    Ok(Some(files))
 }
 
-fn open_file<P: AsRef<Path>>(path: P) -> Result<File, Error> {
-    OpenOptions::new()
-        .read(true)
-        .create(true)
-        .append(true)
-        .open(&path)
-        .map_err(|err| {
-            Error::OsError(
-                format!(
-                    "While opening log file {:?}, we received an error from the OS: {}",
-                    path.as_ref(),
-                    err
-                ),
-                err,
-            )
-        })
+pub trait LogCompacter {
+    fn compact_log(&self) -> Result<(), Error>;
+
+    /// Compacts the log, sealing it, and every record after it, with `cipher` instead of the
+    /// cipher the db was opened with, see `cipher`. `None` leaves the log unsealed.
+    fn rotate_cipher(&self, cipher: Option<Arc<dyn Cipher>>) -> Result<(), Error>;
+
+    /// The size of the log, and how much was written to it since it was last compacted, which
+    /// `Options::compaction` is checked against.
+    fn log_stats(&self) -> Result<LogStats, Error>;
+
+    /// Checks `Options::compaction` every `time_between_compacts`, and compacts the log when it's
+    /// due, on every tick without a policy. The compacter runs until the returned handle is
+    /// stopped or dropped.
+    fn start_background_compacter(
+        &self,
+        time_between_compacts: Duration,
+    ) -> Result<CompacterHandle, Error>;
+}
+
+/// What the bytes handed to a `Replay` are, see `LogFiles`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReplayFrom {
+    /// Records appended to the file that was read last.
+    Appended,
+    /// The whole file that follows the one that was read last.
+    NextFile,
+    /// The oldest file of a log that had to be read again, the tables have to be reset first.
+    Reset,
+}
+
+/// Replays records that other processes wrote to the log, see `Locking::Shared`. Called with the
+/// new bytes, what they are, and the cipher and compressor the records are sealed and compressed
+/// with. Returns how many of the bytes formed complete records.
+pub type Replay = dyn Fn(&[u8], ReplayFrom, Option<&dyn Cipher>, Option<&dyn Compressor>) -> Result<u64, Error>
+    + Send
+    + Sync;
+
+/// What the header of every file of a log records about the schema that wrote it.
+#[derive(Clone, Copy, Debug)]
+pub struct LogSchema {
+    pub tables: &'static [TableInfo],
+    pub version: u32,
+    pub codec: &'static str,
+}
+
+impl LogSchema {
+    fn header(
+        &self,
+        compression: Option<&Compression>,
+        encrypted: bool,
+        generation: u64,
+    ) -> Result<Vec<u8>, Error> {
+        let compressor = compression.map(|compression| compression.compressor.name());
+        header::encode(
+            self.tables,
+            self.version,
+            self.codec,
+            encrypted,
+            compressor,
+            generation,
+        )
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Writer {
+    log: Arc<Mutex<LogFile>>,
+    path: Arc<PathBuf>,
+    durability: Durability,
+    buffer: Option<BufferPolicy>,
+    syncer: Arc<Syncer>,
+    lock: Arc<DbLock>,
+    replay: Arc<OnceLock<ReplayFn>>,
+    schema: LogSchema,
+    compression: Option<Compression>,
+    /// Held while a snapshot is written, so that this process writes one at a time.
+    compacting: Arc<Mutex<()>>,
+    policy: Option<CompactionPolicy>,
+    segment_bytes: Option<u64>,
+    max_record_bytes: Option<u64>,
+}
+
+struct ReplayFn(Box<Replay>);
+
+/// The live segment of the log, and the records that have been appended to it but are still
+/// waiting in memory.
+#[derive(Debug)]
+struct LogFile {
+    file: Arc<File>,
+    buffer: Vec<u8>,
+    buffered_records: u64,
+    buffered_since: Option<Instant>,
+    /// How much of the log this process has seen, anything past it was written by another process
+    read_offset: u64,
+    /// Seals the records appended to the log, and opens the ones read from it.
+    cipher: Option<Arc<dyn Cipher>>,
+    /// Of the live segment, see `LogFiles`.
+    generation: u64,
+    stats: LogStats,
+}
+
+/// Bytes `LogFile::read_new` found, and what they are.
+struct Chunk {
+    bytes: Vec<u8>,
+    from: ReplayFrom,
+    /// Whether they're from the live segment, whose `read_offset` they advance.
+    live: bool,
+}
+
+/// Exclusive access to the log, see `Writer::begin_write`.
+pub struct WriteGuard<'a> {
+    writer: &'a Writer,
+    log: MutexGuard<'a, LogFile>,
+    _writes: WriteLock<'a>,
+}
+
+/// A record `WriteGuard::write` wrote, which is only acknowledged once it's been synced.
+#[must_use = "the record isn't durable until it's synced"]
+pub struct Written<'a> {
+    writer: &'a Writer,
+    /// What to fsync, and how far, with `Durability::GroupCommit`.
+    sync: Option<(u64, Arc<File>)>,
+}
+
+/// A live segment that `WriteGuard::seal` sealed, whose snapshot is still to be written.
+pub struct Sealed {
+    writer: Writer,
+    generation: u64,
+    cipher: Option<Arc<dyn Cipher>>,
+}
+
+/// The log of a db opened with `init_read_only`. It never writes to the log or takes the db's
+/// lock, so it can follow a db that another process has open.
+#[derive(Clone, Debug)]
+pub struct ReadOnlyLog {
+    log: Arc<Mutex<LogFile>>,
+    path: Arc<PathBuf>,
+    incomplete_write: bool,
+    replay: Arc<OnceLock<ReplayFn>>,
+    compressor: Option<Arc<dyn Compressor>>,
+}
+
+impl Writer {
+    pub fn init<P: AsRef<Path>>(
+        file: File,
+        path: P,
+        lock: Arc<DbLock>,
+        options: &Options,
+        schema: LogSchema,
+        generation: u64,
+    ) -> Result<Self, Error> {
+        let durability = options.durability.clone();
+        let buffer = match (&durability, lock.locking()) {
+            // These can't acknowledge a write before it's on disk, so there's nothing to buffer
+            (Durability::EveryCommit | Durability::GroupCommit, _) => None,
+            // Other processes have to see every write as soon as it's made
+            (_, Locking::Shared) => None,
+            (Durability::Never | Durability::Interval(_), Locking::Exclusive) => {
+                options.buffer.clone()
+            }
+        };
+
+        let mut log = LogFile::new(file, options.cipher.clone(), generation)?;
+        if log.read_offset == 0 {
+            let compression = options.compression.as_ref();
+            let header = schema.header(compression, log.cipher.is_some(), generation)?;
+            write_bytes(&log.file, &header)?;
+            log.read_offset = header.len() as u64;
+        }
+        log.measure(path.as_ref())?;
+
+        let writer = Self {
+            log: Arc::new(Mutex::new(log)),
+            path: Arc::new(path.as_ref().to_path_buf()),
+            durability,
+            buffer,
+            syncer: Arc::new(Syncer::default()),
+            lock,
+            replay: Arc::new(OnceLock::new()),
+            schema,
+            compression: options.compression.clone(),
+            compacting: Arc::new(Mutex::new(())),
+            policy: options.compaction.clone(),
+            segment_bytes: options.segment_bytes,
+            max_record_bytes: options.max_record_bytes,
+        };
+
+        writer.spawn_background_flusher();
+
+        Ok(writer)
+    }
+
+    /// Registers how records written by other processes are applied to the tables. Must only
+    /// hold weak references to the tables, or the tables and the writer would keep each other
+    /// alive.
+    #[doc(hidden)]
+    pub fn on_replay(&self, replay: Box<Replay>) {
+        let _ = self.replay.set(ReplayFn(replay));
+    }
+
+    /// Locks the log for writing. In `Locking::Shared` this also waits for other processes to
+    /// finish writing, and replays what they wrote, so that the tables are up to date before
+    /// they're modified. Lock the log before locking any table.
+    pub fn begin_write(&self) -> Result<WriteGuard<'_>, Error> {
+        let mut log = self.lock_log()?;
+        let writes = self.lock.lock_writes()?;
+        self.catch_up(&mut log)?;
+
+        Ok(WriteGuard {
+            writer: self,
+            log,
+            _writes: writes,
+        })
+    }
+
+    pub fn append<S: Serialize>(&self, data: &S) -> Result<(), Error> {
+        self.begin_write()?.append(data)
+    }
+
+    pub fn append_all<S: Serialize>(&self, data: Vec<S>) -> Result<(), Error> {
+        self.begin_write()?.append_all(data)
+    }
+
+    /// Picks up whatever other processes wrote to the log. A no-op in `Locking::Exclusive`.
+    pub fn refresh(&self) -> Result<(), Error> {
+        if self.lock.locking() == Locking::Exclusive {
+            return Ok(());
+        }
+
+        self.begin_write().map(|_| ())
+    }
+
+    /// Writes out any buffered records.
+    pub fn flush(&self) -> Result<(), Error> {
+        let mut log = self.lock_log()?;
+        log.flush(&self.syncer)
+    }
+
+    pub fn sync(&self) -> Result<(), Error> {
+        let mut log = self.lock_log()?;
+        log.flush(&self.syncer)?;
+        let file = log.file.clone();
+        drop(log);
+
+        let seq = self.syncer.stats()?.writes;
+        self.syncer.sync_through(seq, &file)
+    }
+
+    pub fn pause_syncs(&self) -> Result<SyncPause, Error> {
+        self.syncer.pause()
+    }
+
+    pub fn durability_stats(&self) -> Result<DurabilityStats, Error> {
+        let log = self.lock_log()?;
+        let mut stats = self.syncer.stats()?;
+        stats.buffered_writes = log.buffered_records;
+        Ok(stats)
+    }
+
+    pub fn compact_log<S: Serialize>(&self, data: Vec<S>) -> Result<(), Error> {
+        self.begin_write()?.compact_log(data)
+    }
+
+    pub fn log_stats(&self) -> Result<LogStats, Error> {
+        Ok(self.lock_log()?.stats)
+    }
+
+    /// Whether the background compacter compacts on this tick, always without a
+    /// `CompactionPolicy`.
+    pub fn compaction_due(&self) -> Result<bool, Error> {
+        match &self.policy {
+            Some(policy) => Ok(policy.triggered(&self.log_stats()?)),
+            None => Ok(true),
+        }
+    }
+
+    fn catch_up(&self, log: &mut LogFile) -> Result<(), Error> {
+        if self.lock.locking() == Locking::Exclusive {
+            return Ok(());
+        }
+        let replay = match self.replay.get() {
+            Some(replay) => replay,
+            None => return Ok(()),
+        };
+
+        let chunks = log.read_new(&self.path, true)?;
+        if chunks.is_empty() {
+            return Ok(());
+        }
+        let compressor = self.compression.as_ref().map(|c| &*c.compressor);
+        let torn = replay.replay(log, chunks, compressor)?;
+
+        if torn > 0 {
+            // We hold the write lock, so this is what a process that crashed mid-write left behind
+            warn!(
+                "log {:?} ends with a torn write, truncating {} bytes",
+                self.path, torn
+            );
+            log.file.set_len(log.read_offset).map_err(|err| {
+                Error::OsError(
+                    format!("Failed to truncate the torn tail of the log: {}", err),
+                    err,
+                )
+            })?;
+        }
+        if log.read_offset == 0 {
+            // A process crashed while it replaced the live segment
+            let header = self.header(log.cipher.is_some(), log.generation)?;
+            write_bytes(&log.file, &header)?;
+            log.read_offset = header.len() as u64;
+        }
+
+        log.measure(&self.path)
+    }
+
+    fn header(&self, encrypted: bool, generation: u64) -> Result<Vec<u8>, Error> {
+        self.schema
+            .header(self.compression.as_ref(), encrypted, generation)
+    }
+
+    /// Writes the live segment of `generation` to a temporary file next to the log, to be
+    /// renamed over it.
+    fn create_live(
+        &self,
+        generation: u64,
+        cipher: Option<&dyn Cipher>,
+    ) -> Result<(File, PathBuf), Error> {
+        let path = temporary(&numbered(&self.path, LIVE, generation));
+        remove_if_exists(&path)?;
+        let live = open_file(&path)?;
+        write_bytes(&live, &self.header(cipher.is_some(), generation)?)?;
+        sync_file(&live, &path)?;
+
+        Ok((live, path))
+    }
+
+    /// Writes `data` as the snapshot of `generation`, see `LogFiles`, `false` if a newer one was
+    /// written first. The snapshot is written to a temporary file and fsynced before it's renamed
+    /// into place, and the directory is fsynced after, so that a crash at any point leaves either
+    /// no snapshot of `generation` or a complete one. The files it covers are left for
+    /// `replace_covered`.
+    fn write_snapshot<S: Serialize>(
+        &self,
+        data: Vec<S>,
+        generation: u64,
+        cipher: Option<&dyn Cipher>,
+    ) -> Result<bool, Error> {
+        let path = self.path.as_ref();
+        let snapshot = numbered(path, SNAPSHOT, generation);
+        let tmp = temporary(&snapshot);
+
+        // Left over from a compaction that crashed, appending to it would corrupt the snapshot
+        remove_if_exists(&tmp)?;
+        let file = open_file(&tmp)?;
+        write_bytes(&file, &self.header(cipher.is_some(), generation)?)?;
+        if let Err(err) = self.write_chunked(&file, data, cipher) {
+            remove_if_exists(&tmp)?;
+            return Err(err);
+        }
+        sync_file(&file, &tmp)?;
+
+        // A compaction that sealed a later segment finished first
+        if list_numbered(path, SNAPSHOT, "")?.last() > Some(&generation) {
+            remove_if_exists(&tmp)?;
+            return Ok(false);
+        }
+        fs::rename(&tmp, &snapshot).map_err(|err| {
+            Error::OsError(
+                format!(
+                    "Failed to move the snapshot {:?} into place, error: {:?}.",
+                    tmp, err
+                ),
+                err,
+            )
+        })?;
+        sync_dir(path)?;
+
+        Ok(true)
+    }
+
+    /// Lists the files of the log in the manifest, now that the snapshot of `generation` is in
+    /// place, then removes the files it covers, see `LogFiles`.
+    fn replace_covered(&self, generation: u64) -> Result<(), Error> {
+        write_manifest(&self.path)?;
+        remove_covered(&self.path, generation)
+    }
+
+    /// Writes out buffers that have been waiting longer than `BufferPolicy::max_age`, and fsyncs
+    /// for `Durability::Interval`, until the last clone of this writer is dropped.
+    fn spawn_background_flusher(&self) {
+        let interval = match self.durability {
+            Durability::Interval(interval) => Some(interval),
+            _ => None,
+        };
+        let max_age = self.buffer.as_ref().map(|policy| policy.max_age);
+        let tick = match (interval, max_age) {
+            (Some(interval), Some(max_age)) => interval.min(max_age),
+            (Some(tick), None) | (None, Some(tick)) => tick,
+            (None, None) => return,
+        };
+
+        let log = Arc::downgrade(&self.log);
+        let syncer = Arc::downgrade(&self.syncer);
+        thread::spawn(move || {
+            let mut last_sync = Instant::now();
+            loop {
+                thread::sleep(tick);
+
+                let (log, syncer) = match (log.upgrade(), syncer.upgrade()) {
+                    (Some(log), Some(syncer)) => (log, syncer),
+                    _ => return,
+                };
+
+                let result = log.lock().map_err(Error::lock_error).and_then(|mut log| {
+                    if max_age
+                        .filter(|max_age| log.buffer_age() >= *max_age)
+                        .is_some()
+                    {
+                        log.flush(&syncer)?;
+                    }
+
+                    match interval {
+                        Some(interval) if last_sync.elapsed() >= interval => {
+                            last_sync = Instant::now();
+                            let file = log.file.clone();
+                            drop(log);
+                            let stats = syncer.stats()?;
+                            if stats.synced_writes < stats.writes {
+                                syncer.sync_through(stats.writes, &file)?;
+                            }
+                            Ok(())
+                        }
+                        _ => Ok(()),
+                    }
+                });
+
+                if let Err(err) = result {
+                    error!("failed to flush the log in the background: {:?}", err);
+                }
+            }
+        });
+    }
+
+    fn lock_log(&self) -> Result<MutexGuard<'_, LogFile>, Error> {
+        self.log
+            .lock()
+            .map_err(|err| Error::LockError(format!("Writer lock poisoned, this suggest an internal, unexpected, database error. Error: {}", err)))
+    }
+
+    /// Writes `data` to `file` as batches of about `SNAPSHOT_CHUNK` bytes, and no more than
+    /// `max_record_bytes`, so that no record of a snapshot is larger than it has to be, and only
+    /// one is held in memory at a time.
+    fn write_chunked<S: Serialize>(
+        &self,
+        file: &File,
+        data: Vec<S>,
+        cipher: Option<&dyn Cipher>,
+    ) -> Result<(), Error> {
+        let type_name = std::any::type_name::<LogItems<S>>();
+        let overhead = bincode::serialized_size(&LogItems::<S>::Batch(vec![]))
+            .map_err(|err| Error::serialize(type_name, err))?;
+        let limit = self
+            .max_record_bytes
+            .map_or(SNAPSHOT_CHUNK, |max| max.min(SNAPSHOT_CHUNK))
+            .saturating_sub(overhead);
+
+        let mut chunk = vec![];
+        let mut chunk_bytes = 0;
+        let mut chunks = 0;
+        for entry in data {
+            let size =
+                bincode::serialized_size(&entry).map_err(|err| Error::serialize(type_name, err))?;
+            if !chunk.is_empty() && chunk_bytes + size > limit {
+                let batch = LogItems::Batch(std::mem::take(&mut chunk));
+                write_bytes(file, &self.encode(&batch, cipher)?)?;
+                chunk_bytes = 0;
+                chunks += 1;
+            }
+            chunk.push(entry);
+            chunk_bytes += size;
+        }
+        // An empty snapshot is still one record, of no entries
+        if !chunk.is_empty() || chunks == 0 {
+            write_bytes(file, &self.encode(&LogItems::Batch(chunk), cipher)?)?;
+        }
+
+        Ok(())
+    }
+
+    /// Encodes a record, compressed, sealed, and framed, see `frame`.
+    fn encode<S: Serialize>(
+        &self,
+        data: &LogItems<S>,
+        cipher: Option<&dyn Cipher>,
+    ) -> Result<Vec<u8>, Error> {
+        let type_name = std::any::type_name::<LogItems<S>>();
+        let mut data = bincode::serialize(data).map_err(|err| Error::serialize(type_name, err))?;
+        if let Some(max) = self.max_record_bytes.filter(|max| data.len() as u64 > *max) {
+            return Err(Error::RecordTooLarge(format!(
+                "A record of {} is {} bytes, more than the {} bytes `max_record_bytes` allows.",
+                type_name,
+                data.len(),
+                max
+            )));
+        }
+        let compressed = self
+            .compression
+            .as_ref()
+            .and_then(|compression| compression.compress(&data));
+        let is_compressed = compressed.is_some();
+        if let Some(compressed) = compressed {
+            data = compressed;
+        }
+        if let Some(cipher) = cipher {
+            data = cipher.seal(&data).map_err(|err| {
+                Error::serialize(
+                    type_name,
+                    Box::new(bincode::ErrorKind::Custom(err.to_string())),
+                )
+            })?;
+        }
+
+        Ok(frame::encode(&data, is_compressed))
+    }
+}
+
+impl<'a> WriteGuard<'a> {
+    pub fn append<S: Serialize>(self, data: &S) -> Result<(), Error> {
+        self.write(data)?.sync()
+    }
+
+    pub fn append_all<S: Serialize>(self, data: Vec<S>) -> Result<(), Error> {
+        self.write_all(data)?.sync()
+    }
+
+    /// Like `append`, but gives up the log before waiting for `Durability::GroupCommit`, so that
+    /// the caller can give up whatever else it holds before it waits, see `Written::sync`.
+    pub fn write<S: Serialize>(self, data: &S) -> Result<Written<'a>, Error> {
+        self.write_items(&LogItems::Single(data))
+    }
+
+    pub fn write_all<S: Serialize>(self, data: Vec<S>) -> Result<Written<'a>, Error> {
+        self.write_items(&LogItems::Batch(data))
+    }
+
+    /// Replaces every file of the log with a snapshot of `data`, and a new, empty, live segment,
+    /// keeping writers waiting until it's done. `seal` keeps them waiting for less, but leaves
+    /// the segment it sealed in place until its snapshot is written.
+    pub fn compact_log<S: Serialize>(self, data: Vec<S>) -> Result<(), Error> {
+        let cipher = self.log.cipher.clone();
+        self.rotate_cipher(data, cipher)
+    }
+
+    /// Like `compact_log`, but the snapshot, and every record appended after it, is sealed with
+    /// `cipher`, or not at all if it's `None`.
+    pub fn rotate_cipher<S: Serialize>(
+        mut self,
+        data: Vec<S>,
+        cipher: Option<Arc<dyn Cipher>>,
+    ) -> Result<(), Error> {
+        let writer = self.writer;
+        let path = writer.path.as_ref();
+        let generation = self.log.generation;
+
+        // The snapshot covers the live segment, so a crash before it's replaced leaves it obsolete
+        let written = writer.write_snapshot(data, generation, cipher.as_deref())?;
+        let (live, live_path) = writer.create_live(generation + 1, cipher.as_deref())?;
+        rename(&live_path, path)?;
+        sync_dir(path)?;
+        if written {
+            writer.replace_covered(generation)?;
+        }
+
+        // Buffered records are already part of the snapshot
+        self.log.discard_buffer();
+        self.log.read_offset = file_len(&live)?;
+        self.log.file = Arc::new(live);
+        self.log.generation = generation + 1;
+        self.log.cipher = cipher;
+        self.log.stats.records = 0;
+        self.log.measure(path)?;
+        writer.syncer.synced_all()?;
+
+        Ok(())
+    }
+
+    /// Seals the live segment and starts the next one, which is all of a compaction that keeps
+    /// writers waiting, see `LogFiles`. `Sealed::compact` then writes the snapshot of the sealed
+    /// segment, from the files before it.
+    pub fn seal(mut self) -> Result<Sealed, Error> {
+        let generation = self.roll()?;
+
+        Ok(Sealed {
+            writer: self.writer.clone(),
+            generation,
+            cipher: self.log.cipher.clone(),
+        })
+    }
+
+    /// Seals the live segment and starts the next one, returns the generation of the sealed one.
+    fn roll(&mut self) -> Result<u64, Error> {
+        let writer = self.writer;
+        let path = writer.path.as_ref();
+        let generation = self.log.generation;
+
+        // Nothing is appended to a sealed segment, so whatever it's missing now is lost
+        self.log.flush(&writer.syncer)?;
+        sync_file(&self.log.file, path)?;
+        let (live, live_path) = writer.create_live(generation + 1, self.log.cipher.as_deref())?;
+        rename(path, &numbered(path, SEGMENT, generation))?;
+        rename(&live_path, path)?;
+        sync_dir(path)?;
+
+        self.log.read_offset = file_len(&live)?;
+        self.log.file = Arc::new(live);
+        self.log.generation = generation + 1;
+        self.log.stats.records = 0;
+        write_manifest(path)?;
+        self.log.measure(path)?;
+        writer.syncer.synced_all()?;
+
+        Ok(generation)
+    }
+
+    /// Rolls the live segment over once it's grown past `Options::segment_bytes`.
+    fn roll_if_full(&mut self) -> Result<(), Error> {
+        match self.writer.segment_bytes {
+            Some(max) if file_len(&self.log.file)? >= max => self.roll().map(|_| ()),
+            _ => Ok(()),
+        }
+    }
+
+    fn write_items<S: Serialize>(mut self, data: &LogItems<S>) -> Result<Written<'a>, Error> {
+        let writer = self.writer;
+        let record = writer.encode(data, self.log.cipher.as_deref())?;
+        self.log.stats.records += 1;
+        self.log.stats.segment_bytes += record.len() as u64;
+
+        if let Some(policy) = &writer.buffer {
+            self.log.buffer(record);
+            if self.log.buffer.len() >= policy.max_bytes || self.log.buffer_age() >= policy.max_age
+            {
+                self.log.flush(&writer.syncer)?;
+                self.roll_if_full()?;
+            }
+            return Ok(Written { writer, sync: None });
+        }
+
+        write_bytes(&self.log.file, &record)?;
+        self.log.read_offset += record.len() as u64;
+        let seq = writer.syncer.wrote(1)?;
+        // Fsyncs the record along with the segment it's in
+        self.roll_if_full()?;
+
+        let sync = match writer.durability {
+            Durability::EveryCommit => {
+                writer.syncer.sync_through(seq, &self.log.file)?;
+                None
+            }
+            // Other writers append while we wait, so they can join our fsync
+            Durability::GroupCommit => Some((seq, self.log.file.clone())),
+            Durability::Never | Durability::Interval(_) => None,
+        };
+
+        Ok(Written { writer, sync })
+    }
+}
+
+impl Written<'_> {
+    /// Waits until the record is as durable as `Durability` requires.
+    pub fn sync(self) -> Result<(), Error> {
+        match self.sync {
+            Some((seq, file)) => self.writer.syncer.sync_through(seq, &file),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Sealed {
+    /// Writes the snapshot of the sealed segment, and removes the files it covers. `restore`
+    /// replays the files before the new live segment, see `Sealed::files`, into the entries of
+    /// the snapshot, so that writers don't wait for them to be read. This process still only
+    /// writes one snapshot at a time.
+    pub fn compact<S, F>(self, restore: F) -> Result<(), Error>
+    where
+        S: Serialize,
+        F: FnOnce(&LogFiles, Option<&dyn Cipher>, Option<&dyn Compressor>) -> Result<Vec<S>, Error>,
+    {
+        let writer = &self.writer;
+        let _compacting = writer.compacting.lock().map_err(Error::lock_error)?;
+        let files = match self.files()? {
+            Some(files) => files,
+            None => return Ok(()),
+        };
+        let compressor = writer
+            .compression
+            .as_ref()
+            .map(|compression| compression.compressor.as_ref());
+        let data = restore(&files, self.cipher.as_deref(), compressor)?;
+        drop(files);
+        let written = writer.write_snapshot(data, self.generation, self.cipher.as_deref())?;
+
+        let mut log = writer.lock_log()?;
+        if written {
+            // Writers, in this process or others, replace the manifest too
+            let _writes = writer.lock.lock_writes()?;
+            writer.replace_covered(self.generation)?;
+        }
+        log.measure(&writer.path)
+    }
+
+    /// The files the snapshot of the sealed segment covers: the latest snapshot before it, if
+    /// there is one, the segments after that, and the sealed segment itself as the live one.
+    /// `None` if a newer snapshot already covers it. They're opened with the log locked, so that
+    /// nothing removes them first, and read after.
+    fn files(&self) -> Result<Option<LogFiles>, Error> {
+        let writer = &self.writer;
+        let path = writer.path.as_ref();
+        let _log = writer.lock_log()?;
+        let _writes = writer.lock.lock_writes()?;
+
+        let snapshots = list_numbered(path, SNAPSHOT, "")?;
+        if snapshots.last() >= Some(&self.generation) {
+            return Ok(None);
+        }
+        let covered = snapshots.last().copied().unwrap_or(0);
+        let mut sealed = vec![];
+        if covered > 0 {
+            sealed.extend(open_if_exists(&numbered(path, SNAPSHOT, covered))?);
+        }
+        for generation in covered + 1..=self.generation {
+            let segment = numbered(path, SEGMENT, generation);
+            match open_if_exists(&segment)? {
+                Some(segment) => sealed.push(segment),
+                None => {
+                    return Err(Error::CorruptLog(format!(
+                        "The segment {:?} of the log is missing, the records that were in it are \
+                        lost.",
+                        segment
+                    )))
+                }
+            }
+        }
+
+        Ok(sealed.pop().map(|live| LogFiles {
+            sealed,
+            live,
+            obsolete_live: false,
+            generation: self.generation,
+        }))
+    }
+}
+
+impl ReadOnlyLog {
+    /// `read_offset` is where the last complete record of `file`, the live segment of
+    /// `generation`, ends.
+    pub fn init<P: AsRef<Path>>(
+        file: File,
+        path: P,
+        read_offset: u64,
+        generation: u64,
+        cipher: Option<Arc<dyn Cipher>>,
+        compressor: Option<Arc<dyn Compressor>>,
+    ) -> Result<Self, Error> {
+        let mut log = LogFile::new(file, cipher, generation)?;
+        let incomplete_write = log.read_offset > read_offset;
+        log.read_offset = read_offset;
+
+        Ok(Self {
+            log: Arc::new(Mutex::new(log)),
+            path: Arc::new(path.as_ref().to_path_buf()),
+            incomplete_write,
+            replay: Arc::new(OnceLock::new()),
+            compressor,
+        })
+    }
+
+    /// Registers how new records are applied to the tables, see `Writer::on_replay`.
+    #[doc(hidden)]
+    pub fn on_replay(&self, replay: Box<Replay>) {
+        let _ = self.replay.set(ReplayFn(replay));
+    }
+
+    /// Whether the log ended with a torn write when it was opened. It's skipped, not truncated.
+    pub fn incomplete_write(&self) -> bool {
+        self.incomplete_write
+    }
+
+    /// Picks up whatever was written to the log since it was last read. A record that's still
+    /// being written is left for the next refresh.
+    pub fn refresh(&self) -> Result<(), Error> {
+        let replay = match self.replay.get() {
+            Some(replay) => replay,
+            None => return Ok(()),
+        };
+
+        let mut log = self
+            .log
+            .lock()
+            .map_err(|err| Error::LockError(format!("Reader lock poisoned, this suggest an internal, unexpected, database error. Error: {}", err)))?;
+        let chunks = log.read_new(&self.path, false)?;
+        replay.replay(&mut log, chunks, self.compressor.as_deref())?;
+
+        Ok(())
+    }
+}
+
+impl ReplayFn {
+    /// Replays `chunks` in order. Returns how many bytes at the end of the live segment didn't
+    /// form a complete record, `log.read_offset` stops before them.
+    fn replay(
+        &self,
+        log: &mut LogFile,
+        chunks: Vec<Chunk>,
+        compressor: Option<&dyn Compressor>,
+    ) -> Result<u64, Error> {
+        let mut torn = 0;
+        for chunk in chunks {
+            let valid = (self.0)(&chunk.bytes, chunk.from, log.cipher.as_deref(), compressor)?;
+            if chunk.live {
+                log.read_offset = match chunk.from {
+                    ReplayFrom::Appended => log.read_offset + valid,
+                    ReplayFrom::NextFile | ReplayFrom::Reset => valid,
+                };
+                torn = chunk.bytes.len() as u64 - valid;
+            }
+        }
+
+        Ok(torn)
+    }
+}
+
+impl fmt::Debug for ReplayFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ReplayFn")
+    }
+}
+
+impl LogFile {
+    fn new(file: File, cipher: Option<Arc<dyn Cipher>>, generation: u64) -> Result<Self, Error> {
+        Ok(Self {
+            read_offset: file_len(&file)?,
+            cipher,
+            generation,
+            stats: LogStats::default(),
+            file: Arc::new(file),
+            buffer: vec![],
+            buffered_records: 0,
+            buffered_since: None,
+        })
+    }
+
+    /// Reads whatever was written to the log at `path` since `read_offset`, in the order it has
+    /// to be replayed in. If a compaction in another process replaced the live segment, the rest
+    /// of it is read, then the files that followed it. If those can't be followed, because a
+    /// compaction removed them, or if the live segment was cut short, every file of the log is
+    /// read again, see `read_files`.
+    fn read_new(&mut self, path: &Path, writable: bool) -> Result<Vec<Chunk>, Error> {
+        let len = file_len(&self.file)?;
+        if len < self.read_offset {
+            return self.reload(path, writable);
+        }
+
+        let replaced = !same_file(&self.file, path)?;
+        let mut chunks = vec![];
+        if len > self.read_offset {
+            chunks.push(Chunk {
+                bytes: read_range(&self.file, self.read_offset, len)?,
+                from: ReplayFrom::Appended,
+                live: !replaced,
+            });
+        }
+        if !replaced {
+            return Ok(chunks);
+        }
+
+        let file = open_live(path, writable)?;
+        let live = read_range(&file, 0, file_len(&file)?)?;
+        let generation = match header::generation(&live) {
+            Some(generation) if generation > self.generation => generation,
+            _ => return self.reload(path, writable),
+        };
+        for segment in self.generation + 1..generation {
+            match read_if_exists(&numbered(path, SEGMENT, segment))? {
+                Some(bytes) => chunks.push(Chunk {
+                    bytes,
+                    from: ReplayFrom::NextFile,
+                    live: false,
+                }),
+                None => return self.reload(path, writable),
+            }
+        }
+        chunks.push(Chunk {
+            bytes: live,
+            from: ReplayFrom::NextFile,
+            live: true,
+        });
+        self.file = Arc::new(file);
+        self.generation = generation;
+
+        Ok(chunks)
+    }
+
+    /// Reads every file of the log again, the first of them resets the tables.
+    fn reload(&mut self, path: &Path, writable: bool) -> Result<Vec<Chunk>, Error> {
+        let (file, files) = read_files(open_live(path, writable)?, path, writable)?;
+        self.file = Arc::new(file);
+        self.generation = files.generation;
+        self.read_offset = 0;
+
+        let mut chunks = vec![];
+        for file in &files.sealed {
+            chunks.push(Chunk {
+                bytes: file.read_all()?,
+                from: ReplayFrom::NextFile,
+                live: false,
+            });
+        }
+        if files.obsolete_live {
+            self.read_offset = files.live.len();
+        } else {
+            chunks.push(Chunk {
+                bytes: files.live.read_all()?,
+                from: ReplayFrom::NextFile,
+                live: true,
+            });
+        }
+        if let Some(first) = chunks.first_mut() {
+            first.from = ReplayFrom::Reset;
+        }
+
+        Ok(chunks)
+    }
+
+    /// Measures the files of the log at `path` again, after they changed other than by this
+    /// process appending to them, see `LogStats`.
+    fn measure(&mut self, path: &Path) -> Result<(), Error> {
+        let records = self.stats.records;
+        self.stats = measure(path)?;
+        self.stats.segment_bytes += self.buffer.len() as u64;
+        self.stats.records = records;
+
+        Ok(())
+    }
+
+    fn buffer(&mut self, record: Vec<u8>) {
+        if self.buffer.is_empty() {
+            self.buffered_since = Some(Instant::now());
+        }
+        self.buffer.extend(record);
+        self.buffered_records += 1;
+    }
+
+    fn buffer_age(&self) -> Duration {
+        self.buffered_since
+            .map(|since| since.elapsed())
+            .unwrap_or_default()
+    }
+
+    fn flush(&mut self, syncer: &Syncer) -> Result<(), Error> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+
+        write_bytes(&self.file, &self.buffer)?;
+        syncer.wrote(self.buffered_records)?;
+        self.discard_buffer();
+
+        Ok(())
+    }
+
+    fn discard_buffer(&mut self) {
+        self.buffer.clear();
+        self.buffered_records = 0;
+        self.buffered_since = None;
+    }
+}
+
+impl Drop for LogFile {
+    fn drop(&mut self) {
+        if self.buffer.is_empty() {
+            return;
+        }
+
+        if let Err(err) = write_bytes(&self.file, &self.buffer) {
+            error!("failed to flush buffered log records on drop: {:?}", err);
+        }
+    }
+}
+
+fn file_len(file: &File) -> Result<u64, Error> {
+    Ok(file
+        .metadata()
+        .map_err(|err| {
+            Error::OsError(
+                format!("Failed to read the length of the log: {}", err),
+                err,
+            )
+        })?
+        .len())
+}
+
+fn read_range(mut file: &File, from: u64, to: u64) -> Result<Vec<u8>, Error> {
+    let mut bytes = vec![0; (to - from) as usize];
+    file.seek(SeekFrom::Start(from))
+        .and_then(|_| file.read_exact(&mut bytes))
+        .map_err(|err| {
+            Error::OsError(
+                format!("Failed to read bytes {}..{} of the log: {}", from, to, err),
+                err,
+            )
+        })?;
+
+    Ok(bytes)
+}
+
+/// `None` if there's no file at `path`, a compaction may have removed it.
+fn open_if_exists(path: &Path) -> Result<Option<LogSource>, Error> {
+    match File::open(path) {
+        Ok(file) => {
+            let len = file_len(&file)?;
+            LogSource::new(file, len).map(Some)
+        }
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(Error::OsError(
+            format!("Failed to open {:?}, error: {}", path, err),
+            err,
+        )),
+    }
+}
+
+/// A handle to `file` that's read independently of it, for `LogSource`.
+fn clone_file(file: &File) -> Result<File, Error> {
+    file.try_clone().map_err(|err| {
+        Error::OsError(
+            format!("Failed to open the live segment again, error: {}", err),
+            err,
+        )
+    })
+}
+
+/// `None` if there's no file at `path`, a compaction may have removed it.
+fn read_if_exists(path: &Path) -> Result<Option<Vec<u8>>, Error> {
+    match fs::read(path) {
+        Ok(bytes) => Ok(Some(bytes)),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(Error::OsError(
+            format!("Failed to read {:?}, error: {}", path, err),
+            err,
+        )),
+    }
+}
+
+/// The file of the log at `path` of the kind `kind` and `generation`, see `LogFiles`.
+fn numbered(path: &Path, kind: &str, generation: u64) -> PathBuf {
+    with_suffix(path, &format!("{}.{}", kind, generation))
+}
+
+/// Where a file is written before it's renamed to `path`.
+fn temporary(path: &Path) -> PathBuf {
+    with_suffix(path, "tmp")
+}
+
+/// `path` followed by `.suffix`. Unlike `Path::with_extension`, it keeps whatever follows a dot
+/// in the name of the log, so that logs named `app.users` and `app.files` don't share files.
+pub(crate) fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// The generations of the files `numbered(path, kind, _)` followed by `suffix`, in order.
+fn list_numbered(path: &Path, kind: &str, suffix: &str) -> Result<Vec<u64>, Error> {
+    let prefix = with_suffix(path, &format!("{}.", kind));
+    let prefix = prefix.file_name().unwrap_or_default().to_string_lossy();
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let to_error = |err: std::io::Error| {
+        Error::OsError(
+            format!("Failed to list the files of the log in {:?}: {}", dir, err),
+            err,
+        )
+    };
+
+    let mut generations = vec![];
+    for entry in fs::read_dir(dir).map_err(to_error)? {
+        let name = entry.map_err(to_error)?.file_name();
+        let generation = name
+            .to_str()
+            .and_then(|name| name.strip_prefix(prefix.as_ref()))
+            .and_then(|rest| rest.strip_suffix(suffix))
+            .and_then(|generation| generation.parse::<u64>().ok());
+        generations.extend(generation);
+    }
+    generations.sort_unstable();
+
+    Ok(generations)
+}
+
+/// Removes the files of the log that the snapshot of `generation` covers, see `LogFiles`, and
+/// the snapshots older than it, including any that a crash left half written.
+fn remove_covered(path: &Path, generation: u64) -> Result<(), Error> {
+    for snapshot in list_numbered(path, SNAPSHOT, "")? {
+        if snapshot < generation {
+            remove_if_exists(&numbered(path, SNAPSHOT, snapshot))?;
+        }
+    }
+    for snapshot in list_numbered(path, SNAPSHOT, ".tmp")? {
+        if snapshot <= generation {
+            remove_if_exists(&temporary(&numbered(path, SNAPSHOT, snapshot)))?;
+        }
+    }
+    for segment in list_numbered(path, SEGMENT, "")? {
+        if segment <= generation {
+            remove_if_exists(&numbered(path, SEGMENT, segment))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The sizes of the files of the log at `path`, see `LogStats`.
+fn measure(path: &Path) -> Result<LogStats, Error> {
+    let len = |path: &Path| match fs::metadata(path) {
+        Ok(metadata) => Ok(metadata.len()),
+        // Removed by a compaction in another process
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(0),
+        Err(err) => Err(Error::OsError(
+            format!("Failed to read the length of {:?}, error: {}", path, err),
+            err,
+        )),
+    };
+
+    let (snapshot, segments) = current_files(path)?;
+    let mut stats = LogStats::default();
+    if let Some(snapshot) = snapshot {
+        stats.snapshot_bytes = len(&snapshot)?;
+    }
+    for segment in segments {
+        stats.segment_bytes += len(&segment)?;
+    }
+
+    Ok(stats)
+}
+
+/// The newest snapshot of the log at `path`, and the segments after it, ending with the live
+/// one, see `LogFiles`.
+fn current_files(path: &Path) -> Result<(Option<PathBuf>, Vec<PathBuf>), Error> {
+    let covered = list_numbered(path, SNAPSHOT, "")?.pop().unwrap_or(0);
+    let snapshot = Some(numbered(path, SNAPSHOT, covered)).filter(|_| covered > 0);
+    let mut segments: Vec<_> = list_numbered(path, SEGMENT, "")?
+        .into_iter()
+        .filter(|segment| *segment > covered)
+        .map(|segment| numbered(path, SEGMENT, segment))
+        .collect();
+    segments.push(path.to_path_buf());
+
+    Ok((snapshot, segments))
+}
+
+/// Replaces the manifest of the log at `path` with a list of its current files, see `LogFiles`.
+/// Written to a temporary file first, so that the manifest is always complete.
+fn write_manifest(path: &Path) -> Result<(), Error> {
+    let (snapshot, segments) = current_files(path)?;
+    let names: String = snapshot
+        .iter()
+        .chain(&segments)
+        .filter_map(|file| file.file_name())
+        .map(|name| format!("{}\n", name.to_string_lossy()))
+        .collect();
+
+    let manifest = with_suffix(path, MANIFEST);
+    let tmp = temporary(&manifest);
+    remove_if_exists(&tmp)?;
+    let file = open_file(&tmp)?;
+    write_bytes(&file, names.as_bytes())?;
+    sync_file(&file, &tmp)?;
+    rename(&tmp, &manifest)?;
+    sync_dir(path)
+}
+
+/// Opens the live segment of the log at `path`. Another process replacing it renames two files,
+/// a reader that looks in between finds neither, and looks again.
+fn open_live(path: &Path, writable: bool) -> Result<File, Error> {
+    if writable {
+        return open_file(path);
+    }
+    for _ in 0..10 {
+        match File::open(path) {
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                thread::sleep(Duration::from_millis(10))
+            }
+            _ => break,
+        }
+    }
+    open_read_only(path)
+}
+
+fn rename(from: &Path, to: &Path) -> Result<(), Error> {
+    fs::rename(from, to).map_err(|err| {
+        Error::OsError(
+            format!("Failed to rename {:?} to {:?}, error: {:?}.", from, to, err),
+            err,
+        )
+    })
+}
+
+fn sync_file(file: &File, path: &Path) -> Result<(), Error> {
+    file.sync_all()
+        .map_err(|err| Error::OsError(format!("Failed to fsync {:?}, error: {}", path, err), err))
+}
+
+/// Whether `file` is still the file at `path`.
+#[cfg(unix)]
+fn same_file(file: &File, path: &Path) -> Result<bool, Error> {
+    use std::os::unix::fs::MetadataExt;
+
+    let open = file.metadata();
+    let current = fs::metadata(path);
+    match (open, current) {
+        (Ok(open), Ok(current)) => Ok(open.dev() == current.dev() && open.ino() == current.ino()),
+        (_, Err(err)) if err.kind() == ErrorKind::NotFound => Ok(false),
+        (Err(err), _) | (_, Err(err)) => Err(Error::OsError(
+            format!(
+                "Failed to compare the open log to {:?}, error: {}",
+                path, err
+            ),
+            err,
+        )),
+    }
+}
+
+/// Without a portable file identity, assume the log was replaced, which reloads it in full.
+#[cfg(not(unix))]
+fn same_file(_file: &File, _path: &Path) -> Result<bool, Error> {
+    Ok(false)
+}
+
+pub(crate) fn remove_if_exists(path: &Path) -> Result<(), Error> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != ErrorKind::NotFound => Err(Error::OsError(
+            format!("Failed to remove {:?}, error: {}", path, err),
+            err,
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// fsyncs the directory containing `path`, which makes a rename into it durable.
+#[cfg(unix)]
+fn sync_dir(path: &Path) -> Result<(), Error> {
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    File::open(dir)
+        .and_then(|dir| dir.sync_all())
+        .map_err(|err| {
+            Error::OsError(
+                format!("Failed to fsync the directory {:?}, error: {}", dir, err),
+                err,
+            )
+        })
+}
+
+/// Directories can't be opened as files here, renames are made durable by the file system.
+#[cfg(not(unix))]
+fn sync_dir(_path: &Path) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Copies the files of the log before they're rewritten, see `LogFiles`, each to its name
+/// followed by `.extension`.
+#[doc(hidden)]
+pub fn keep_copy(path: &Path, extension: &str) -> Result<Vec<PathBuf>, Error> {
+    let mut files = vec![(path.to_path_buf(), with_suffix(path, extension))];
+    for kind in [SNAPSHOT, SEGMENT] {
+        for generation in list_numbered(path, kind, "")? {
+            let file = numbered(path, kind, generation);
+            files.push((file.clone(), with_suffix(&file, extension)));
+        }
+    }
+
+    for (file, copy) in &files {
+        warn!("keeping a copy of the log file {:?} at {:?}", file, copy);
+        fs::copy(file, copy)
+            .and_then(|_| File::open(copy))
+            .and_then(|copy| copy.sync_all())
+            .map_err(|err| {
+                Error::OsError(
+                    format!("Failed to copy the log to {:?}, error: {}", copy, err),
+                    err,
+                )
+            })?;
+    }
+    sync_dir(path)?;
+
+    Ok(files.into_iter().map(|(_, copy)| copy).collect())
+}
+
+/// Moves a log from its legacy path to `path`, unless there's already a log at `path`.
+fn migrate_legacy_log(legacy: &Path, path: &Path) -> Result<(), Error> {
+    if legacy == path || !legacy.exists() {
+        return Ok(());
+    }
+    if path.exists() {
+        warn!(
+            "ignoring the log at the legacy path {:?}, the log at {:?} is used instead",
+            legacy, path
+        );
+        return Ok(());
+    }
+
+    warn!(
+        "moving the log at the legacy path {:?} to {:?}",
+        legacy, path
+    );
+    match fs::rename(legacy, path) {
+        // Another process got to it first
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(Error::OsError(
+            format!(
+                "Failed to move the log from {:?} to {:?}, error: {}",
+                legacy, path, err
+            ),
+            err,
+        )),
+        Ok(()) => sync_dir(path),
+    }
+}
+
+fn write_bytes(mut file: &File, bytes: &[u8]) -> Result<(), Error> {
+    file.write_all(bytes).map_err(|err| {
+        Error::OsError(
+            format!(
+                "Failed to append {} bytes to the log, error: {}",
+                bytes.len(),
+                err
+            ),
+            err,
+        )
+    })
+}
+
+fn open_file<P: AsRef<Path>>(path: P) -> Result<File, Error> {
+    OpenOptions::new()
+        .read(true)
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|err| {
+            Error::OsError(
+                format!(
+                    "While opening log file {:?}, we received an error from the OS: {}",
+                    path.as_ref(),
+                    err
+                ),
+                err,
+            )
+        })
+}
+
+fn open_read_only<P: AsRef<Path>>(path: P) -> Result<File, Error> {
+    File::open(&path).map_err(|err| {
+        Error::OsError(
+            format!(
+                "While opening log file {:?} read only, we received an error from the OS: {}",
+                path.as_ref(),
+                err
+            ),
+            err,
+        )
+    })
 }
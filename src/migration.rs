@@ -0,0 +1,145 @@
+//! Migrating a log written by an older version of a schema.
+//!
+//! A schema names the schema it replaces with `from`, and converts the tables of its predecessor
+//! by implementing `Migration`:
+//!
+//! ```no_run
+//! use hmdb::log::Reader;
+//! use serde::{Deserialize, Serialize};
+//!
+//! mod v1 {
+//!     hmdb::schema! {
+//!         SchemaV1 {
+//!             accounts: <String, String>,
+//!             files: <u64, Vec<u8>>
+//!         }
+//!     }
+//! }
+//!
+//! #[derive(Clone, Debug, Serialize, Deserialize)]
+//! pub struct Account {
+//!     name: String,
+//! }
+//!
+//! impl From<String> for Account {
+//!     fn from(name: String) -> Self {
+//!         Account { name }
+//!     }
+//! }
+//!
+//! mod v2 {
+//!     use hmdb::errors::Error;
+//!     use hmdb::migration::{convert, Migration};
+//!
+//!     use super::v1::{self, SchemaV1};
+//!     use super::Account;
+//!
+//!     hmdb::schema! {
+//!         SchemaV2 from SchemaV1 {
+//!             accounts: <String, Account>,
+//!             files: <u64, Vec<u8>>
+//!         }
+//!     }
+//!
+//!     impl Migration for SchemaV2 {
+//!         type Previous = SchemaV1;
+//!
+//!         fn migrate(previous: v1::tables::SchemaV1) -> Result<tables::SchemaV2, Error> {
+//!             Ok(tables::SchemaV2 {
+//!                 accounts: convert(previous.accounts),
+//!                 files: previous.files,
+//!             })
+//!         }
+//!     }
+//! }
+//!
+//! fn main() {
+//!     let db = v2::SchemaV2::init("db_dir").unwrap();
+//! }
+//! ```
+//!
+//! Every `schema!` declares modules of its own, `tables` among them, so each version goes in a
+//! module of its own.
+//!
+//! `SchemaV2` opens the log of `SchemaV1`. If the log was written by `SchemaV1`, or by any schema
+//! before it, it's loaded by that schema and migrated one version at a time. `init` then rewrites
+//! the log as a snapshot of the current version, keeping a copy of the old files next to them,
+//! each named after its file followed by `.v<old version>`, until the rewrite succeeded.
+//! `init_read_only` migrates in memory only, and can't `refresh` until the log was rewritten.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::path::Path;
+
+use crate::cipher::Cipher;
+use crate::compression::Compressor;
+use crate::errors::Error;
+use crate::header;
+use crate::header::Parsed;
+use crate::log;
+use crate::log::LogFiles;
+use crate::recovery::{RecoveryPolicy, RecoveryReport};
+
+/// A schema that can load its tables from a log written by it, or by one of its predecessors.
+/// Implemented by `schema!`.
+pub trait Versioned {
+    /// The tables of the schema as plain maps, `tables::SchemaName`.
+    type Tables;
+
+    /// Loads the tables from every file of a log, migrating them if the log was written by a
+    /// predecessor. `cipher` opens the records of a sealed log, `compressor` decompresses those of
+    /// a log it compressed. With more than one of `threads`, the records are decoded, and the
+    /// tables filled, on threads of their own, see `Options::replay_threads`.
+    fn load(
+        log: &LogFiles,
+        policy: &RecoveryPolicy,
+        cipher: Option<&dyn Cipher>,
+        compressor: Option<&dyn Compressor>,
+        threads: usize,
+    ) -> Result<(Self::Tables, RecoveryReport), Error>;
+}
+
+/// Converts the tables of the schema named with `from` into the tables of this one.
+pub trait Migration: Versioned {
+    type Previous: Versioned;
+
+    fn migrate(previous: <Self::Previous as Versioned>::Tables) -> Result<Self::Tables, Error>;
+}
+
+/// Converts the keys and values of a table with their `From` impls.
+pub fn convert<K, V, K2, V2>(table: HashMap<K, V>) -> HashMap<K2, V2>
+where
+    K2: From<K> + Eq + Hash,
+    V2: From<V>,
+{
+    table
+        .into_iter()
+        .map(|(key, value)| (key.into(), value.into()))
+        .collect()
+}
+
+/// The version of the schema that wrote a log, `None` if it can't be told. Headerless logs were
+/// written by the first one.
+pub fn schema_version(buffer: &[u8]) -> Option<u32> {
+    match header::read(buffer) {
+        Parsed::Header { header, .. } => Some(header.version),
+        Parsed::Missing if !buffer.is_empty() => Some(1),
+        _ => None,
+    }
+}
+
+/// Keeps a copy of the files of the log written by `version` while `rewrite` replaces them, and
+/// removes the copies once the rewrite succeeded.
+#[doc(hidden)]
+pub fn with_backup<F>(path: &Path, version: u32, rewrite: F) -> Result<(), Error>
+where
+    F: FnOnce() -> Result<(), Error>,
+{
+    let backup = log::keep_copy(path, &format!("v{}", version))?;
+    rewrite()?;
+    for copy in backup {
+        log::remove_if_exists(&copy)?;
+    }
+
+    Ok(())
+}
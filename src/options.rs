@@ -0,0 +1,257 @@
+use std::marker::PhantomData;
+use std::path::Path;
+use std::sync::Arc;
+
+use serde::de::DeserializeOwned;
+
+use crate::cipher::Cipher;
+use crate::compaction::CompactionPolicy;
+use crate::compression::Compression;
+use crate::durability::{BufferPolicy, Durability};
+use crate::errors::Error;
+use crate::lock::Locking;
+use crate::log::Reader;
+use crate::recovery::{Progress, RecoveryPolicy, ReplayProgress};
+
+/// Everything that can be configured about how a db is opened and how it behaves once it's open.
+/// `Options::default()` is what `init` uses.
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    pub recovery: RecoveryPolicy,
+    pub durability: Durability,
+    pub buffer: Option<BufferPolicy>,
+    pub locking: Locking,
+    /// Seals every record of the log, see `cipher`.
+    pub cipher: Option<Arc<dyn Cipher>>,
+    /// Compresses large records, see `compression`.
+    pub compression: Option<Compression>,
+    /// When the background compacter compacts, see `compaction`.
+    pub compaction: Option<CompactionPolicy>,
+    /// Rolls the live segment over to a new one once it's this many bytes, see `log::LogFiles`.
+    /// Without a limit, it's only rolled over by compactions.
+    pub segment_bytes: Option<u64>,
+    /// Decodes the records of the log on this many threads when the db is opened, and fills each
+    /// table on a thread of its own, see `Reader::parse_files`. The tables end up the same as
+    /// when they're filled on the thread opening the db, which 0, the default, and 1 do.
+    pub replay_threads: usize,
+    /// Refuses to write a record, a write, a transaction, or an entry of a snapshot, whose
+    /// encoded size is more than this many bytes, with `Error::RecordTooLarge`. Without a limit,
+    /// records of any size are written, those of more than 4 GiB in a wider frame.
+    pub max_record_bytes: Option<u64>,
+    /// Names the log, and the files next to it, see `log::LogFiles`, instead of the name the
+    /// schema gives it, see `schema!`. A log opened with a name isn't looked for under its legacy
+    /// name, see `Reader::legacy_log_path`.
+    pub log_name: Option<String>,
+}
+
+/// Builds the options a db is opened with, and validates them before opening it. Start one with
+/// `Reader::options`:
+///
+/// ```ignore, rust
+/// let db = SchemaName::options()
+///     .sync(Durability::GroupCommit)
+///     .open("db_dir")
+///     .unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct OpenOptions<Schema> {
+    options: Options,
+    schema: PhantomData<fn() -> Schema>,
+}
+
+impl Options {
+    /// Checks for settings that contradict each other, or make no sense on their own. `init_with`
+    /// refuses options that don't pass.
+    pub fn validate(&self) -> Result<(), Error> {
+        if let Durability::Interval(interval) = self.durability {
+            if interval.is_zero() {
+                return Err(Error::InvalidOptions(
+                    "The fsync interval of `Durability::Interval` must be longer than zero."
+                        .to_string(),
+                ));
+            }
+        }
+
+        if let Some(buffer) = &self.buffer {
+            if matches!(
+                self.durability,
+                Durability::EveryCommit | Durability::GroupCommit
+            ) {
+                return Err(Error::InvalidOptions(format!(
+                    "Writes can't be buffered with `Durability::{:?}`, which writes out every \
+                    commit before acknowledging it.",
+                    self.durability
+                )));
+            }
+            if self.locking == Locking::Shared {
+                return Err(Error::InvalidOptions(
+                    "Writes can't be buffered with `Locking::Shared`, other processes have to \
+                    see every write as soon as it's made."
+                        .to_string(),
+                ));
+            }
+            if buffer.max_age.is_zero() {
+                return Err(Error::InvalidOptions(
+                    "The `max_age` of a `BufferPolicy` must be longer than zero.".to_string(),
+                ));
+            }
+        }
+
+        if self.segment_bytes == Some(0) {
+            return Err(Error::InvalidOptions(
+                "`segment_bytes` must be more than zero, or every record would get a segment of \
+                its own."
+                    .to_string(),
+            ));
+        }
+
+        if self.max_record_bytes == Some(0) {
+            return Err(Error::InvalidOptions(
+                "`max_record_bytes` must be more than zero, or no record could be written."
+                    .to_string(),
+            ));
+        }
+
+        if let Some(name) = &self.log_name {
+            if name.is_empty()
+                || name == "."
+                || name == ".."
+                || name.contains(|c| std::path::is_separator(c) || c == '\0')
+            {
+                return Err(Error::InvalidOptions(format!(
+                    "The `log_name` {:?} isn't the name of a file, the log is always kept in the \
+                    directory the db is opened in.",
+                    name
+                )));
+            }
+        }
+
+        if let Some(ratio) = self.compaction.as_ref().and_then(|policy| policy.ratio) {
+            if ratio.is_nan() || ratio <= 1.0 {
+                return Err(Error::InvalidOptions(format!(
+                    "The `ratio` of a `CompactionPolicy` must be more than 1, a log is never \
+                    smaller than its live data, so {} would compact on every check.",
+                    ratio
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<Schema> OpenOptions<Schema> {
+    pub fn new() -> Self {
+        Self {
+            options: Options::default(),
+            schema: PhantomData,
+        }
+    }
+
+    /// Replaces the whole recovery policy, including a `progress` callback, or `seal_plaintext`,
+    /// set before it.
+    pub fn recovery(mut self, recovery: RecoveryPolicy) -> Self {
+        self.options.recovery = recovery;
+        self
+    }
+
+    /// Sets `RecoveryPolicy::progress`.
+    pub fn progress<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(ReplayProgress) + Send + Sync + 'static,
+    {
+        self.options.recovery.progress = Some(Progress::new(callback));
+        self
+    }
+
+    /// Sets `RecoveryPolicy::seal_plaintext`.
+    pub fn seal_plaintext(mut self) -> Self {
+        self.options.recovery.seal_plaintext = true;
+        self
+    }
+
+    pub fn sync(mut self, durability: Durability) -> Self {
+        self.options.durability = durability;
+        self
+    }
+
+    pub fn buffer(mut self, buffer: BufferPolicy) -> Self {
+        self.options.buffer = Some(buffer);
+        self
+    }
+
+    pub fn locking(mut self, locking: Locking) -> Self {
+        self.options.locking = locking;
+        self
+    }
+
+    pub fn cipher(mut self, cipher: Arc<dyn Cipher>) -> Self {
+        self.options.cipher = Some(cipher);
+        self
+    }
+
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.options.compression = Some(compression);
+        self
+    }
+
+    pub fn compaction(mut self, policy: CompactionPolicy) -> Self {
+        self.options.compaction = Some(policy);
+        self
+    }
+
+    pub fn segment_bytes(mut self, segment_bytes: u64) -> Self {
+        self.options.segment_bytes = Some(segment_bytes);
+        self
+    }
+
+    pub fn replay_threads(mut self, threads: usize) -> Self {
+        self.options.replay_threads = threads;
+        self
+    }
+
+    pub fn max_record_bytes(mut self, max_record_bytes: u64) -> Self {
+        self.options.max_record_bytes = Some(max_record_bytes);
+        self
+    }
+
+    pub fn log_name(mut self, name: impl Into<String>) -> Self {
+        self.options.log_name = Some(name.into());
+        self
+    }
+
+    /// The validated options, for `Reader::init_with`.
+    pub fn build(self) -> Result<Options, Error> {
+        self.options.validate()?;
+        Ok(self.options)
+    }
+
+    pub fn open<OnDisk, P>(self, path: P) -> Result<Schema, Error>
+    where
+        Schema: Reader<OnDisk, Schema>,
+        OnDisk: DeserializeOwned + Send,
+        P: AsRef<Path>,
+    {
+        Schema::init_with(path, self.options)
+    }
+
+    /// Opens the db with `Reader::init_read_only_with`, where only the recovery policy, the
+    /// cipher, the compressor, and the log name apply.
+    pub fn open_read_only<OnDisk, P>(
+        self,
+        path: P,
+    ) -> Result<<Schema as Reader<OnDisk, Schema>>::ReadOnly, Error>
+    where
+        Schema: Reader<OnDisk, Schema>,
+        OnDisk: DeserializeOwned + Send,
+        P: AsRef<Path>,
+    {
+        Schema::init_read_only_with(path, self.options)
+    }
+}
+
+impl<Schema> Default for OpenOptions<Schema> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
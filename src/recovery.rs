@@ -0,0 +1,158 @@
+use std::fmt;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How many bytes of the log are replayed between calls to `RecoveryPolicy::progress`.
+pub const PROGRESS_BYTES: u64 = 1 << 20;
+
+/// Controls what `init` does when it finds a damaged log.
+#[derive(Clone, Debug, Default)]
+pub struct RecoveryPolicy {
+    /// Before a torn tail is truncated away, copy its bytes to a side file next to the log so
+    /// that they can be inspected later.
+    pub preserve_torn_tail: bool,
+
+    /// Skip records that fail their checksum or fail to deserialize, instead of refusing to open
+    /// the db. Whatever could be read is loaded, and the skipped byte ranges are listed in the
+    /// `RecoveryReport`. The damaged bytes stay in the log until the next `compact_log`, which is
+    /// how an operator accepts the partial data.
+    pub salvage: bool,
+
+    /// Read a log that isn't sealed when the db is opened with a `Cipher`, instead of refusing it
+    /// with `Error::Decryption`, and seal it if the db is opened for writing. Only for a log known
+    /// to have been written without a cipher, anyone can write one.
+    pub seal_plaintext: bool,
+
+    /// Called while the log is replayed, every `PROGRESS_BYTES` and at the end of each of its
+    /// files, so that a slow start can show how far along it is. Called on the thread opening the
+    /// db.
+    pub progress: Option<Progress>,
+}
+
+/// How far replaying the log has got, see `RecoveryPolicy::progress`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ReplayProgress {
+    /// Bytes of the log replayed so far, counting through its files, oldest first.
+    pub bytes_read: u64,
+
+    /// Bytes in every file of the log together.
+    pub total_bytes: u64,
+
+    /// Records replayed so far.
+    pub records: u64,
+}
+
+/// The callback of `RecoveryPolicy::progress`.
+#[derive(Clone)]
+pub struct Progress {
+    callback: Arc<dyn Fn(ReplayProgress) + Send + Sync>,
+    /// What the files before the one being replayed add up to.
+    before: ReplayProgress,
+}
+
+/// Describes what `init` had to do to bring the log back into a consistent state.
+#[derive(Clone, Debug, Default)]
+pub struct RecoveryReport {
+    /// Length of the live segment of the log, in bytes, that was kept after recovery, see
+    /// `log::LogFiles`.
+    pub valid_bytes: u64,
+
+    /// Number of bytes at the end of the log that did not form a complete record and were
+    /// removed. A non-zero value usually means the process crashed during a write.
+    pub truncated_bytes: u64,
+
+    /// Where the truncated bytes were saved, if `RecoveryPolicy::preserve_torn_tail` was set.
+    pub torn_tail: Option<PathBuf>,
+
+    /// Byte ranges of the log that were skipped because they could not be read, counting through
+    /// its files, oldest first, as if they were one. Only populated when `RecoveryPolicy::salvage`
+    /// is set.
+    pub skipped: Vec<Range<u64>>,
+
+    /// Copies of the files of the log, with the `skipped` bytes still in them, kept when the log
+    /// was rewritten because of a change of format, cipher, compressor, or value types. hmdb never
+    /// removes them, they're there until whoever opened the db is done with them.
+    pub kept_copies: Vec<PathBuf>,
+
+    /// The format the log was written in, 0 for logs from before the header was introduced. A
+    /// log in an older format than `log::FORMAT_VERSION` is rewritten in the current one when
+    /// the db is opened for writing.
+    pub format_version: u32,
+
+    /// The version of the schema that wrote the log, see `migration`. A log written by a
+    /// predecessor of the schema opening it is migrated, and rewritten when the db is opened for
+    /// writing.
+    pub schema_version: u32,
+
+    /// Tables whose records hold an earlier value type, which were converted to the current one
+    /// while they were read. The log is rewritten in the current type when the db is opened for
+    /// writing.
+    pub upcast_tables: Vec<String>,
+
+    /// Whether the records of the log were sealed by a `Cipher`. A log that wasn't is sealed
+    /// when the db is opened for writing with a cipher.
+    pub encrypted: bool,
+
+    /// `Compressor::name` of the compressor the log is compressed by, if it's compressed. A log
+    /// is recompressed when the db is opened for writing with another `Compression`, or without.
+    pub compressor: Option<String>,
+
+    /// Number of records replayed, in every file of the log.
+    pub records: u64,
+
+    /// Number of bytes read from the files of the log, including any that were skipped or
+    /// truncated.
+    pub bytes_read: u64,
+
+    /// Every table of the schema, with the number of entries it held once the log was replayed.
+    pub table_entries: Vec<(String, usize)>,
+
+    /// Time spent opening the db, from reading the log to rewriting it, if it had to be.
+    pub elapsed: Duration,
+}
+
+impl Progress {
+    pub fn new<F>(callback: F) -> Self
+    where
+        F: Fn(ReplayProgress) + Send + Sync + 'static,
+    {
+        Self {
+            callback: Arc::new(callback),
+            before: ReplayProgress::default(),
+        }
+    }
+
+    /// The callback for a file of the log that starts where `before` ends.
+    pub(crate) fn after(&self, before: ReplayProgress) -> Self {
+        Self {
+            callback: self.callback.clone(),
+            before,
+        }
+    }
+
+    /// Reports `bytes_read` bytes and `records` records into the current file.
+    pub(crate) fn report(&self, bytes_read: u64, records: u64) {
+        (self.callback)(ReplayProgress {
+            bytes_read: self.before.bytes_read + bytes_read,
+            total_bytes: self.before.total_bytes,
+            records: self.before.records + records,
+        })
+    }
+}
+
+impl fmt::Debug for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Progress")
+            .field("before", &self.before)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RecoveryReport {
+    /// `true` if the log was opened without needing any repairs.
+    pub fn is_clean(&self) -> bool {
+        self.truncated_bytes == 0 && self.skipped.is_empty()
+    }
+}
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 use std::marker::PhantomData;
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, RwLock, Weak};
 
 use crate::errors::Error;
 use crate::log::{SchemaEvent, Writer};
@@ -55,32 +55,94 @@ where
         Ok(val)
     }
 
+    /// The table isn't changed if the write can't be logged, see `Error::RecordTooLarge`, and
+    /// isn't held while the write is synced, see `WriteGuard::write`.
     pub fn insert(&self, key: K, val: V) -> Result<Option<V>, Error> {
-        let prior = self
-            .data
-            .write()
-            .map_err(Error::lock_error)?
-            .insert(key.clone(), val.clone());
+        let log = self.writer.begin_write()?;
+        let mut data = self.data.write().map_err(Error::lock_error)?;
 
-        let s = Log::insert(key, val);
-        self.writer.append(&s)?;
+        let s = Log::insert(key.clone(), val.clone());
+        let written = log.write(&s)?;
+        let prior = data.insert(key, val);
+        drop(data);
 
+        written.sync()?;
         Ok(prior)
     }
 
     pub fn delete(&self, key: K) -> Result<Option<V>, Error> {
-        let prior = self.data.write().map_err(Error::lock_error)?.remove(&key);
+        let log = self.writer.begin_write()?;
+        let mut data = self.data.write().map_err(Error::lock_error)?;
 
-        let s = Log::delete(key);
-        self.writer.append(&s)?;
+        let s = Log::delete(key.clone());
+        let written = log.write(&s)?;
+        let prior = data.remove(&key);
+        drop(data);
 
+        written.sync()?;
         Ok(prior)
     }
 
+    /// Callers must hold `Writer::begin_write` first, the log is always locked before a table.
     #[doc(hidden)]
-    pub fn begin_transaction(&self) -> Result<(TransactionTable<K, V, Log>, Writer), Error> {
+    pub fn begin_transaction(&self) -> Result<TransactionTable<'_, K, V, Log>, Error> {
         let data = self.data.write().map_err(Error::lock_error)?;
 
-        Ok((TransactionTable::init(data), self.writer.clone()))
+        Ok(TransactionTable::init(data))
+    }
+
+    #[doc(hidden)]
+    pub fn downgrade(&self) -> Weak<RwLock<HashMap<K, V>>> {
+        Arc::downgrade(&self.data)
+    }
+}
+
+/// A table of a db opened with `init_read_only`, it has no way to write.
+#[derive(Clone, Debug)]
+pub struct ReadOnlyTable<K, V>
+where
+    K: Key,
+    V: Value,
+{
+    data: Arc<RwLock<HashMap<K, V>>>,
+}
+
+impl<K, V> ReadOnlyTable<K, V>
+where
+    K: Key,
+    V: Value,
+{
+    pub fn init(data: HashMap<K, V>) -> Self {
+        let data = Arc::new(RwLock::new(data));
+        Self { data }
+    }
+
+    pub fn get(&self, key: &K) -> Result<Option<V>, Error> {
+        let val = self
+            .data
+            .read()
+            .map_err(Error::lock_error)?
+            .get(key)
+            .cloned();
+        Ok(val)
+    }
+
+    pub fn exists(&self, key: &K) -> Result<bool, Error> {
+        let val = self
+            .data
+            .read()
+            .map_err(Error::lock_error)?
+            .contains_key(key);
+        Ok(val)
+    }
+
+    pub fn get_all(&self) -> Result<HashMap<K, V>, Error> {
+        let val = self.data.read().map_err(Error::lock_error)?.clone();
+        Ok(val)
+    }
+
+    #[doc(hidden)]
+    pub fn downgrade(&self) -> Weak<RwLock<HashMap<K, V>>> {
+        Arc::downgrade(&self.data)
     }
 }
@@ -21,9 +21,18 @@ where
 {
     data: RwLockWriteGuard<'a, HashMap<K, V>>,
     pub pending: Vec<Log::LogEntry>,
+    /// What the changes replaced, most recent last, see `rollback`.
+    undo: Vec<Undo<K, V>>,
     log: PhantomData<Log>,
 }
 
+enum Undo<K, V> {
+    /// The value a key had before it was inserted or deleted.
+    Key(K, Option<V>),
+    /// The whole table before it was cleared.
+    All(HashMap<K, V>),
+}
+
 impl<'a, K, V, Log> TransactionTable<'a, K, V, Log>
 where
     K: Key,
@@ -31,9 +40,12 @@ where
     Log: SchemaEvent<K, V>,
 {
     pub fn init(data: RwLockWriteGuard<'a, HashMap<K, V>>) -> Self {
-        let pending = vec![];
-        let log = PhantomData {};
-        Self { data, pending, log }
+        Self {
+            data,
+            pending: vec![],
+            undo: vec![],
+            log: PhantomData,
+        }
     }
 
     pub fn keys(&self) -> HashSet<&K> {
@@ -54,6 +66,7 @@ where
 
     pub fn insert(&mut self, key: K, val: V) -> Option<V> {
         let prior = self.data.insert(key.clone(), val.clone());
+        self.undo.push(Undo::Key(key.clone(), prior.clone()));
 
         let s = Log::insert(key, val);
         self.pending.push(s);
@@ -63,6 +76,7 @@ where
 
     pub fn delete(&mut self, key: K) -> Option<V> {
         let prior = self.data.remove(&key);
+        self.undo.push(Undo::Key(key.clone(), prior.clone()));
 
         let s = Log::delete(key);
         self.pending.push(s);
@@ -71,9 +85,25 @@ where
     }
 
     pub fn clear(&mut self) {
-        self.data.clear();
+        let all = std::mem::take(&mut *self.data);
+        self.undo.push(Undo::All(all));
 
         let s = Log::clear();
         self.pending.push(s);
     }
+
+    /// Undoes every change made to the table, for a transaction that couldn't be logged.
+    pub fn rollback(&mut self) {
+        while let Some(undo) = self.undo.pop() {
+            match undo {
+                Undo::Key(key, Some(prior)) => {
+                    self.data.insert(key, prior);
+                }
+                Undo::Key(key, None) => {
+                    self.data.remove(&key);
+                }
+                Undo::All(all) => *self.data = all,
+            }
+        }
+    }
 }
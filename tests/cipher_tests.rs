@@ -0,0 +1,259 @@
+#[cfg(test)]
+pub mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+
+    use hmdb::cipher::{Cipher, CipherError};
+    use hmdb::errors::Error;
+    use hmdb::log::{LogCompacter, Reader};
+    use hmdb::recovery::RecoveryPolicy;
+    use uuid::Uuid;
+
+    use crate::tests::db::Db;
+
+    mod db {
+        use hmdb::schema;
+
+        schema! {
+            Db {
+                names: <u64, String>
+            }
+        }
+    }
+
+    const SCHEMA_NAME: &str = "Db";
+
+    /// Xors the record with the key and appends a keyed checksum. Not encryption, but it fails to
+    /// open what another key sealed, which is all these tests need.
+    #[derive(Debug)]
+    pub struct Xor(u8);
+
+    impl Xor {
+        fn tag(&self, record: &[u8]) -> u32 {
+            record.iter().fold(self.0 as u32, |tag, byte| {
+                tag.wrapping_mul(31).wrapping_add(*byte as u32)
+            })
+        }
+    }
+
+    impl Cipher for Xor {
+        fn seal(&self, record: &[u8]) -> Result<Vec<u8>, CipherError> {
+            let mut sealed: Vec<u8> = record.iter().map(|byte| byte ^ self.0).collect();
+            sealed.extend_from_slice(&self.tag(record).to_be_bytes());
+            Ok(sealed)
+        }
+
+        fn open(&self, sealed: &[u8]) -> Result<Vec<u8>, CipherError> {
+            if sealed.len() < 4 {
+                return Err("record too short".into());
+            }
+            let (body, tag) = sealed.split_at(sealed.len() - 4);
+            let record: Vec<u8> = body.iter().map(|byte| byte ^ self.0).collect();
+            if self.tag(&record).to_be_bytes() != tag {
+                return Err("bad tag".into());
+            }
+            Ok(record)
+        }
+    }
+
+    fn test_db() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("target")
+            .join(Uuid::new_v4().to_string())
+    }
+
+    fn open(db_path: &Path, key: u8) -> Result<Db, Error> {
+        Db::options().cipher(Arc::new(Xor(key))).open(db_path)
+    }
+
+    /// Whether any file of the log, the snapshot and segments included, contains `text`.
+    fn contains(db_path: &Path, text: &str) -> bool {
+        fs::read_dir(db_path).unwrap().any(|entry| {
+            let path = entry.unwrap().path();
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            let log = match name.starts_with(SCHEMA_NAME) && !name.ends_with("lock") {
+                true => fs::read(path).unwrap(),
+                false => vec![],
+            };
+            log.windows(text.len()).any(|w| w == text.as_bytes())
+        })
+    }
+
+    fn assert_decryption_error<T>(result: Result<T, Error>) {
+        match result {
+            Err(Error::Decryption(_)) => {}
+            other => panic!("expected Error::Decryption, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn sealed_records_round_trip() {
+        let db_path = &test_db();
+        let db = open(db_path, 7).unwrap();
+        db.names.insert(1, "parth".to_string()).unwrap();
+        drop(db);
+        assert!(!contains(db_path, "parth"));
+
+        let db = open(db_path, 7).unwrap();
+        assert!(db.recovery_report().encrypted);
+        assert_eq!(db.names.get(&1).unwrap().unwrap(), "parth");
+        drop(db);
+
+        let db = Db::options()
+            .cipher(Arc::new(Xor(7)))
+            .open_read_only(db_path)
+            .unwrap();
+        assert_eq!(db.names.get(&1).unwrap().unwrap(), "parth");
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn sealed_log_needs_its_key() {
+        let db_path = &test_db();
+        let db = open(db_path, 7).unwrap();
+        db.names.insert(1, "parth".to_string()).unwrap();
+        drop(db);
+
+        assert_decryption_error(open(db_path, 8));
+        assert_decryption_error(Db::init(db_path));
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn tampered_records_are_refused() {
+        let db_path = &test_db();
+        let db = open(db_path, 7).unwrap();
+        for (key, name) in ["parth", "travis", "smail"].iter().enumerate() {
+            db.names.insert(key as u64, name.to_string()).unwrap();
+        }
+        drop(db);
+        let log_path = db_path.join(SCHEMA_NAME);
+        let log = fs::read(&log_path).unwrap();
+        let records: Vec<_> = log
+            .windows(4)
+            .enumerate()
+            .filter(|(_, bytes)| bytes[..3] == [0xdb, 0x1e, 0x5e])
+            .map(|(index, _)| index)
+            .collect();
+        assert_eq!(records.len(), 3);
+
+        let salvage = || {
+            Db::options()
+                .cipher(Arc::new(Xor(7)))
+                .recovery(RecoveryPolicy {
+                    salvage: true,
+                    ..Default::default()
+                })
+                .open(db_path)
+        };
+        // The last record isn't taken for a torn tail, the middle one isn't skipped
+        for tampered in [log.len() - 1, records[1] + 12] {
+            let mut log = log.clone();
+            log[tampered] ^= 1;
+            fs::write(&log_path, &log).unwrap();
+            assert_decryption_error(open(db_path, 7));
+            assert_decryption_error(salvage());
+        }
+
+        // A record cut short by the end of the log is still what a crash leaves behind
+        fs::write(&log_path, &log[..log.len() - 1]).unwrap();
+        let db = open(db_path, 7).unwrap();
+        assert_eq!(db.names.get_all().unwrap().len(), 2);
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn rotating_the_cipher_reseals_the_log() {
+        let db_path = &test_db();
+        let db = open(db_path, 7).unwrap();
+        db.names.insert(1, "parth".to_string()).unwrap();
+        db.rotate_cipher(Some(Arc::new(Xor(8)))).unwrap();
+        db.names.insert(2, "travis".to_string()).unwrap();
+        drop(db);
+
+        assert_decryption_error(open(db_path, 7));
+        let db = open(db_path, 8).unwrap();
+        assert_eq!(db.names.get(&1).unwrap().unwrap(), "parth");
+        assert_eq!(db.names.get(&2).unwrap().unwrap(), "travis");
+
+        db.rotate_cipher(None).unwrap();
+        drop(db);
+        assert!(contains(db_path, "travis"));
+        assert!(!Db::init(db_path).unwrap().recovery_report().encrypted);
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn plaintext_log_is_refused() {
+        let db_path = &test_db();
+        // Put in place of a sealed log
+        let db = Db::init(db_path).unwrap();
+        db.names.insert(1, "forged".to_string()).unwrap();
+        drop(db);
+        let log = fs::read(db_path.join(SCHEMA_NAME)).unwrap();
+        assert_decryption_error(open(db_path, 7));
+        assert_decryption_error(
+            Db::options()
+                .cipher(Arc::new(Xor(7)))
+                .open_read_only(db_path),
+        );
+        assert_eq!(fs::read(db_path.join(SCHEMA_NAME)).unwrap(), log);
+
+        // Or in place of the live segment, next to a sealed snapshot
+        fs::remove_dir_all(db_path).unwrap();
+        let db = Db::init(db_path).unwrap();
+        // To the generation of the sealed one, which was sealed when it was created
+        db.compact_log().unwrap();
+        db.compact_log().unwrap();
+        db.names.insert(1, "forged".to_string()).unwrap();
+        drop(db);
+        let live = fs::read(db_path.join(SCHEMA_NAME)).unwrap();
+        fs::remove_dir_all(db_path).unwrap();
+        let db = open(db_path, 7).unwrap();
+        db.names.insert(1, "parth".to_string()).unwrap();
+        db.compact_log().unwrap();
+        drop(db);
+        fs::write(db_path.join(SCHEMA_NAME), live).unwrap();
+        assert_decryption_error(open(db_path, 7));
+
+        // Unless it's meant to be sealed, with the rest of the log
+        let db = Db::options()
+            .cipher(Arc::new(Xor(7)))
+            .seal_plaintext()
+            .open(db_path)
+            .unwrap();
+        assert!(!db.recovery_report().encrypted);
+        assert_eq!(db.names.get(&1).unwrap().unwrap(), "forged");
+        drop(db);
+        assert!(!contains(db_path, "forged"));
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn plaintext_log_is_sealed_when_asked_to() {
+        let db_path = &test_db();
+        let db = Db::init(db_path).unwrap();
+        db.names.insert(1, "parth".to_string()).unwrap();
+        drop(db);
+        assert!(contains(db_path, "parth"));
+
+        let db = Db::options()
+            .cipher(Arc::new(Xor(7)))
+            .seal_plaintext()
+            .open(db_path)
+            .unwrap();
+        assert!(!db.recovery_report().encrypted);
+        assert_eq!(db.names.get(&1).unwrap().unwrap(), "parth");
+        drop(db);
+        assert!(!contains(db_path, "parth"));
+        assert!(open(db_path, 7).unwrap().recovery_report().encrypted);
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+}
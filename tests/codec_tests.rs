@@ -0,0 +1,143 @@
+#[cfg(test)]
+pub mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use hmdb::codec::{Codec, CodecError};
+    use hmdb::errors::Error;
+    use hmdb::log::Reader;
+    use serde::de::DeserializeOwned;
+    use serde::Serialize;
+    use uuid::Uuid;
+
+    use crate::tests::binary::Binary;
+    use crate::tests::v1::{Account, Db};
+    use crate::tests::v2::DbV2;
+
+    const SCHEMA_NAME: &str = "Db";
+
+    /// A self-describing codec
+    pub struct Json;
+
+    impl Codec for Json {
+        const NAME: &'static str = "json";
+
+        fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, CodecError> {
+            Ok(serde_json::to_vec(value)?)
+        }
+
+        fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, CodecError> {
+            Ok(serde_json::from_slice(bytes)?)
+        }
+    }
+
+    mod v1 {
+        use super::Json;
+        use hmdb::schema;
+        use serde::{Deserialize, Serialize};
+
+        #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+        pub struct Account {
+            pub name: String,
+        }
+
+        schema! {
+            #[codec = Json]
+            Db {
+                accounts: <u64, Account>
+            }
+        }
+    }
+
+    mod v2 {
+        use super::Json;
+        use hmdb::schema;
+        use serde::{Deserialize, Serialize};
+
+        #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+        pub struct Account {
+            pub name: String,
+            #[serde(default)]
+            pub email: Option<String>,
+        }
+
+        schema! {
+            #[codec = Json]
+            DbV2 as "Db" {
+                accounts: <u64, Account>
+            }
+        }
+    }
+
+    mod binary {
+        use super::v1::Account;
+        use hmdb::schema;
+
+        schema! {
+            Binary as "Db" {
+                accounts: <u64, Account>
+            }
+        }
+    }
+
+    fn test_db() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("target")
+            .join(Uuid::new_v4().to_string())
+    }
+
+    fn populate(db_path: &PathBuf) {
+        let db = Db::init(db_path).unwrap();
+        db.accounts
+            .insert(
+                1,
+                Account {
+                    name: "parth".to_string(),
+                },
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn records_are_written_with_the_codec() {
+        let db_path = &test_db();
+        populate(db_path);
+
+        let log = fs::read(db_path.join(SCHEMA_NAME)).unwrap();
+        assert!(log.windows(16).any(|w| w == br#"{"name":"parth"}"#));
+
+        let db = Db::init(db_path).unwrap();
+        assert_eq!(db.accounts.get(&1).unwrap().unwrap().name, "parth");
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn self_describing_values_gain_optional_fields() {
+        let db_path = &test_db();
+        populate(db_path);
+
+        let db = DbV2::init(db_path).unwrap();
+        let account = db.accounts.get(&1).unwrap().unwrap();
+        assert_eq!(account.name, "parth");
+        assert_eq!(account.email, None);
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn other_codec_is_rejected() {
+        let db_path = &test_db();
+        populate(db_path);
+
+        match Binary::init(db_path) {
+            Err(Error::SchemaMismatch(msg)) => assert!(msg.contains("json"), "{}", msg),
+            other => panic!(
+                "expected Error::SchemaMismatch, got {:?}",
+                other.map(|_| ())
+            ),
+        }
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+}
@@ -0,0 +1,248 @@
+#[cfg(test)]
+pub mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process::{Command, Stdio};
+    use std::thread;
+    use std::time::Duration;
+
+    use hmdb::compaction::{CompactionPolicy, LogStats};
+    use hmdb::log::{LogCompacter, Reader};
+    use uuid::Uuid;
+
+    use crate::tests::schema::Db;
+
+    const SCHEMA_NAME: &str = "Db";
+    const CRASH_DB: &str = "HMDB_CRASH_DB";
+
+    mod schema {
+        use hmdb::schema;
+
+        schema! {
+            Db {
+                table1: <u64, String>,
+                table2: <String, u64>
+            }
+        }
+    }
+
+    fn test_db() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("target")
+            .join(Uuid::new_v4().to_string())
+    }
+
+    #[test]
+    fn stale_compaction_file_is_ignored() {
+        let db_path = &test_db();
+
+        let db = Db::init(db_path).unwrap();
+        db.table1.insert(1, "one".to_string()).unwrap();
+
+        // What a compaction that crashed half way through leaves behind
+        let stale = db_path.join(format!("{}..log_compaction", SCHEMA_NAME));
+        fs::write(&stale, [0xdb, 0x1e, 0x5e, 0xc0, 0, 0, 1]).unwrap();
+
+        drop(db);
+        let db = Db::init(db_path).unwrap();
+        assert_eq!(db.table1.get(&1).unwrap().unwrap(), "one");
+
+        db.compact_log().unwrap();
+        assert!(!stale.exists());
+
+        drop(db);
+        let db = Db::init(db_path).unwrap();
+        assert!(db.recovery_report().is_clean());
+        assert_eq!(db.table1.get(&1).unwrap().unwrap(), "one");
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn log_stats_follow_writes_and_compactions() {
+        let db_path = &test_db();
+
+        let db = Db::init(db_path).unwrap();
+        let empty = db.log_stats().unwrap();
+        assert_eq!(empty.records, 0);
+        assert_eq!(empty.snapshot_bytes, 0);
+
+        for i in 0..10 {
+            db.table1.insert(i, "value".to_string()).unwrap();
+        }
+        let written = db.log_stats().unwrap();
+        assert_eq!(written.records, 10);
+        assert!(written.segment_bytes > empty.segment_bytes);
+
+        db.compact_log().unwrap();
+        let compacted = db.log_stats().unwrap();
+        assert_eq!(compacted.records, 0);
+        assert!(compacted.snapshot_bytes > 0);
+        assert_eq!(compacted.log_bytes(), db.log_stats().unwrap().log_bytes());
+
+        let stats = LogStats {
+            segment_bytes: 300,
+            snapshot_bytes: 100,
+            records: 5,
+        };
+        let policy = |policy: CompactionPolicy| policy.triggered(&stats);
+        assert!(!policy(CompactionPolicy::default()));
+        assert!(policy(CompactionPolicy {
+            log_bytes: Some(400),
+            ..Default::default()
+        }));
+        assert!(policy(CompactionPolicy {
+            ratio: Some(4.0),
+            ..Default::default()
+        }));
+        assert!(!policy(CompactionPolicy {
+            ratio: Some(4.5),
+            ..Default::default()
+        }));
+        assert!(policy(CompactionPolicy {
+            records: Some(5),
+            ..Default::default()
+        }));
+        assert!(!policy(CompactionPolicy {
+            records: Some(5),
+            min_bytes: 401,
+            ..Default::default()
+        }));
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn background_compacter_waits_for_the_policy() {
+        let db_path = &test_db();
+        let snapshot = db_path.join(format!("{}.snapshot.1", SCHEMA_NAME));
+
+        let db = Db::options()
+            .compaction(CompactionPolicy {
+                records: Some(10),
+                ..Default::default()
+            })
+            .open(db_path)
+            .unwrap();
+        let compacter = db
+            .start_background_compacter(Duration::from_millis(5))
+            .unwrap();
+        db.table1.insert(1, "one".to_string()).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        assert!(!snapshot.exists());
+
+        for i in 2..=10 {
+            db.table1.insert(i, "value".to_string()).unwrap();
+        }
+        for _ in 0..100 {
+            if snapshot.exists() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(snapshot.exists());
+        assert_eq!(db.log_stats().unwrap().records, 0);
+        compacter.stop();
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn compacter_handle_controls_the_thread() {
+        let db_path = &test_db();
+        let db = Db::init(db_path).unwrap();
+
+        let compacter = db
+            .start_background_compacter(Duration::from_millis(5))
+            .unwrap();
+        compacter.pause().unwrap();
+        thread::sleep(Duration::from_millis(20));
+        let paused = compacter.stats().unwrap().compactions;
+        for i in 0..100 {
+            db.table1.insert(i, "value".to_string()).unwrap();
+        }
+        db.table1.insert(0, "zero".to_string()).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(compacter.stats().unwrap().compactions, paused);
+
+        compacter.compact_now().unwrap();
+        let stats = compacter.stats().unwrap();
+        assert_eq!(stats.compactions, paused + 1);
+        assert!(stats.last_run.is_some());
+        assert!(stats.bytes_before > stats.bytes_after);
+        assert_eq!(stats.last_error, None);
+
+        compacter.resume().unwrap();
+        for _ in 0..100 {
+            if compacter.stats().unwrap().compactions > paused + 1 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(compacter.stats().unwrap().compactions > paused + 1);
+
+        // Nothing compacts once the handle is gone
+        compacter.stop();
+        let size = fs::metadata(db_path.join(SCHEMA_NAME)).unwrap().len();
+        db.table1.insert(1, "one".to_string()).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        assert!(fs::metadata(db_path.join(SCHEMA_NAME)).unwrap().len() > size);
+        assert_eq!(db.table1.get(&0).unwrap().unwrap(), "zero");
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    /// Run by `compaction_survives_crashes` in a child process, compacts in a loop until killed.
+    #[test]
+    #[ignore]
+    fn crash_child() {
+        let db_path = match env::var(CRASH_DB) {
+            Ok(db_path) => db_path,
+            Err(_) => return,
+        };
+
+        let db = Db::init(db_path).unwrap();
+        for counter in 0.. {
+            db.compact_log().unwrap();
+            db.table2.insert("counter".to_string(), counter).unwrap();
+        }
+    }
+
+    #[test]
+    fn compaction_survives_crashes() {
+        let db_path = &test_db();
+
+        let db = Db::init(db_path).unwrap();
+        for i in 0..2000 {
+            db.table1.insert(i, "value".repeat(10)).unwrap();
+        }
+        drop(db);
+
+        for round in 0..10 {
+            let mut child = Command::new(env::current_exe().unwrap())
+                .args(["tests::crash_child", "--exact", "--ignored"])
+                .env(CRASH_DB, db_path)
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+                .unwrap();
+            thread::sleep(Duration::from_millis(50 + round * 17));
+            child.kill().unwrap();
+            child.wait().unwrap();
+
+            // Whichever log was in place when the child died has to be complete
+            let db = Db::init(db_path).unwrap();
+            assert!(db.recovery_report().skipped.is_empty());
+            let table1 = db.table1.get_all().unwrap();
+            assert_eq!(table1.len(), 2000);
+            assert!(table1.values().all(|value| *value == "value".repeat(10)));
+        }
+
+        // Make sure the children actually got to compact before they were killed
+        let db = Db::init(db_path).unwrap();
+        assert!(db.table2.get(&"counter".to_string()).unwrap().is_some());
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+}
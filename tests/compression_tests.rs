@@ -0,0 +1,162 @@
+#[cfg(test)]
+pub mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+
+    use hmdb::compression::{Compression, CompressionError, Compressor, Lz};
+    use hmdb::errors::Error;
+    use hmdb::log::{LogCompacter, Reader};
+    use uuid::Uuid;
+
+    use crate::tests::db::Db;
+
+    mod db {
+        use hmdb::schema;
+
+        schema! {
+            Db {
+                blobs: <u64, Vec<u8>>
+            }
+        }
+    }
+
+    /// Never makes a record smaller, so it only gets named in the header of the log.
+    #[derive(Debug)]
+    pub struct Identity;
+
+    impl Compressor for Identity {
+        fn name(&self) -> &'static str {
+            "identity"
+        }
+
+        fn compress(&self, record: &[u8]) -> Vec<u8> {
+            record.to_vec()
+        }
+
+        fn decompress(&self, compressed: &[u8]) -> Result<Vec<u8>, CompressionError> {
+            Ok(compressed.to_vec())
+        }
+    }
+
+    fn test_db() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("target")
+            .join(Uuid::new_v4().to_string())
+    }
+
+    /// The size of every file of the log, the snapshot and segments included.
+    fn log_len(db_path: &Path) -> u64 {
+        fs::read_dir(db_path)
+            .unwrap()
+            .map(|entry| entry.unwrap().metadata().unwrap().len())
+            .sum()
+    }
+
+    fn blob() -> Vec<u8> {
+        b"a repetitive value ".repeat(200)
+    }
+
+    #[test]
+    fn lz_round_trips() {
+        let inputs = [
+            vec![],
+            b"abc".to_vec(),
+            vec![7; 10_000],
+            blob(),
+            (0..70_000u32).map(|i| (i * 7 % 251) as u8).collect(),
+        ];
+        for input in inputs {
+            let compressed = Lz.compress(&input);
+            assert_eq!(Lz.decompress(&compressed).unwrap(), input);
+        }
+
+        let compressed = Lz.compress(&blob());
+        assert!(compressed.len() < blob().len() / 10);
+        assert!(Lz.decompress(&compressed[..compressed.len() - 1]).is_err());
+        assert!(Lz.decompress(&[0, 0, 0, 4, 0x80, 0, 1]).is_err());
+    }
+
+    #[test]
+    fn large_records_are_compressed() {
+        let db_path = &test_db();
+        let db = Db::options()
+            .compression(Compression::default())
+            .open(db_path)
+            .unwrap();
+        let before = log_len(db_path);
+        db.blobs.insert(1, blob()).unwrap();
+        db.blobs.insert(2, vec![1, 2, 3]).unwrap();
+        assert!(log_len(db_path) - before < blob().len() as u64 / 10);
+        drop(db);
+
+        let db = Db::init_read_only(db_path).unwrap();
+        assert_eq!(db.recovery_report().compressor.as_deref(), Some("lz"));
+        assert_eq!(db.blobs.get(&1).unwrap().unwrap(), blob());
+        assert_eq!(db.blobs.get(&2).unwrap().unwrap(), vec![1, 2, 3]);
+        drop(db);
+
+        // Opening it without compression decompresses the log
+        let db = Db::init(db_path).unwrap();
+        assert_eq!(db.blobs.get(&1).unwrap().unwrap(), blob());
+        drop(db);
+        let db = Db::init(db_path).unwrap();
+        assert_eq!(db.recovery_report().compressor, None);
+        assert!(log_len(db_path) > blob().len() as u64);
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn compaction_compresses_the_whole_log() {
+        let db_path = &test_db();
+        let compression = Compression {
+            min_size: 4096,
+            ..Default::default()
+        };
+        let db = Db::options()
+            .compression(compression)
+            .open(db_path)
+            .unwrap();
+        for key in 0..100 {
+            db.blobs.insert(key, b"not large".repeat(10)).unwrap();
+        }
+        let uncompressed = log_len(db_path);
+
+        db.compact_log().unwrap();
+        assert!(log_len(db_path) < uncompressed / 4);
+        drop(db);
+
+        let db = Db::init(db_path).unwrap();
+        assert_eq!(db.blobs.get_all().unwrap().len(), 100);
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn custom_compressor_is_needed_to_read_its_log() {
+        let db_path = &test_db();
+        let compression = Compression {
+            compressor: Arc::new(Identity),
+            min_size: 0,
+        };
+        let db = Db::options()
+            .compression(compression.clone())
+            .open(db_path)
+            .unwrap();
+        db.blobs.insert(1, blob()).unwrap();
+        drop(db);
+
+        match Db::init(db_path) {
+            Err(Error::Decompression(msg)) => assert!(msg.contains("identity"), "{}", msg),
+            other => panic!("expected Error::Decompression, got {:?}", other.map(|_| ())),
+        }
+        let db = Db::options()
+            .compression(compression)
+            .open(db_path)
+            .unwrap();
+        assert_eq!(db.blobs.get(&1).unwrap().unwrap(), blob());
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+}
@@ -0,0 +1,336 @@
+#[cfg(test)]
+pub mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::thread;
+    use std::time::Duration;
+
+    use hmdb::durability::{BufferPolicy, Durability, Durable};
+    use hmdb::log::Reader;
+    use hmdb::options::Options;
+    use hmdb::transaction::Transaction;
+    use uuid::Uuid;
+
+    use crate::tests::schema::Db;
+
+    const SCHEMA_NAME: &str = "Db";
+    /// What a log holds before any records are written to it
+    const HEADER_LEN: u64 = 95;
+
+    mod schema {
+        use hmdb::schema;
+
+        schema! {
+            Db {
+                table1: <u64, String>
+            }
+        }
+    }
+
+    fn test_db() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("target")
+            .join(Uuid::new_v4().to_string())
+    }
+
+    fn open(db_path: &Path, durability: Durability) -> Db {
+        Db::init_with(
+            db_path,
+            Options {
+                durability,
+                ..Default::default()
+            },
+        )
+        .unwrap()
+    }
+
+    fn open_buffered(db_path: &Path, buffer: BufferPolicy) -> Db {
+        Db::init_with(
+            db_path,
+            Options {
+                buffer: Some(buffer),
+                ..Default::default()
+            },
+        )
+        .unwrap()
+    }
+
+    fn log_len(db_path: &Path) -> u64 {
+        fs::metadata(db_path.join(SCHEMA_NAME)).unwrap().len()
+    }
+
+    #[test]
+    fn never_leaves_syncing_to_the_os() {
+        let db_path = &test_db();
+
+        let db = open(db_path, Durability::Never);
+        for i in 0..3 {
+            db.table1.insert(i, i.to_string()).unwrap();
+        }
+
+        let stats = db.durability_stats().unwrap();
+        assert_eq!(stats.writes, 3);
+        assert_eq!(stats.synced_writes, 0);
+        assert_eq!(stats.syncs, 0);
+
+        db.sync().unwrap();
+        let stats = db.durability_stats().unwrap();
+        assert_eq!(stats.synced_writes, 3);
+        assert_eq!(stats.syncs, 1);
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn every_commit_syncs_before_acknowledging() {
+        let db_path = &test_db();
+
+        let db = open(db_path, Durability::EveryCommit);
+        for i in 0..3 {
+            db.table1.insert(i, i.to_string()).unwrap();
+            let stats = db.durability_stats().unwrap();
+            assert_eq!(stats.synced_writes, stats.writes);
+        }
+
+        db.transaction(|tx| {
+            tx.table1.insert(10, "10".to_string());
+            tx.table1.insert(11, "11".to_string());
+        })
+        .unwrap();
+
+        let stats = db.durability_stats().unwrap();
+        assert_eq!(stats.writes, 4);
+        assert_eq!(stats.synced_writes, 4);
+        assert_eq!(stats.syncs, 4);
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn interval_syncs_in_the_background() {
+        let db_path = &test_db();
+
+        let db = open(db_path, Durability::Interval(Duration::from_millis(50)));
+        for i in 0..3 {
+            db.table1.insert(i, i.to_string()).unwrap();
+        }
+
+        thread::sleep(Duration::from_millis(300));
+        let stats = db.durability_stats().unwrap();
+        assert_eq!(stats.writes, 3);
+        assert_eq!(stats.synced_writes, 3);
+        assert!(stats.syncs >= 1);
+
+        // An idle db doesn't keep syncing
+        let syncs = stats.syncs;
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(db.durability_stats().unwrap().syncs, syncs);
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn group_commit_syncs_before_acknowledging() {
+        let db_path = &test_db();
+
+        let db = open(db_path, Durability::GroupCommit);
+        let writers: Vec<_> = (0..8)
+            .map(|thread| {
+                let db = db.clone();
+                thread::spawn(move || {
+                    for i in 0..50 {
+                        let before = db.durability_stats().unwrap().writes;
+                        let key = thread * 100 + i;
+                        // Transactions join an fsync as much as single writes do
+                        match thread % 2 {
+                            0 => db.table1.insert(key, i.to_string()).map(|_| ()).unwrap(),
+                            _ => db
+                                .transaction(|tx| {
+                                    tx.table1.insert(key, i.to_string());
+                                })
+                                .unwrap(),
+                        }
+                        assert!(db.durability_stats().unwrap().synced_writes > before);
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let stats = db.durability_stats().unwrap();
+        assert_eq!(stats.writes, 400);
+        assert_eq!(stats.synced_writes, 400);
+        drop(db);
+
+        let db = Db::init(db_path).unwrap();
+        assert_eq!(db.table1.get_all().unwrap().len(), 400);
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn queued_writers_share_an_fsync() {
+        let db_path = &test_db();
+
+        let db = open(db_path, Durability::GroupCommit);
+        let pause = db.pause_syncs().unwrap();
+        let writers: Vec<_> = (0..8)
+            .map(|thread| {
+                let db = db.clone();
+                thread::spawn(move || match thread % 2 {
+                    0 => db
+                        .table1
+                        .insert(thread, thread.to_string())
+                        .map(|_| ())
+                        .unwrap(),
+                    // Transactions give up the tables before they wait, or the others couldn't queue
+                    _ => db
+                        .transaction(|tx| {
+                            tx.table1.insert(thread, thread.to_string());
+                        })
+                        .unwrap(),
+                })
+            })
+            .collect();
+
+        // Every writer appends, then waits for the fsync the pause holds off
+        for _ in 0..500 {
+            if db.durability_stats().unwrap().writes == 8 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let stats = db.durability_stats().unwrap();
+        assert_eq!(stats.writes, 8);
+        assert_eq!(stats.synced_writes, 0);
+        assert_eq!(db.table1.get_all().unwrap().len(), 8);
+
+        drop(pause);
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        let stats = db.durability_stats().unwrap();
+        assert_eq!(stats.synced_writes, 8);
+        assert_eq!(stats.syncs, 1);
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn buffered_writes_wait_for_flush() {
+        let db_path = &test_db();
+
+        let db = open_buffered(
+            db_path,
+            BufferPolicy {
+                max_bytes: usize::MAX,
+                max_age: Duration::from_secs(3600),
+            },
+        );
+        for i in 0..3 {
+            db.table1.insert(i, i.to_string()).unwrap();
+        }
+
+        assert_eq!(log_len(db_path), HEADER_LEN);
+        assert_eq!(db.durability_stats().unwrap().buffered_writes, 3);
+        assert_eq!(db.table1.get_all().unwrap().len(), 3);
+        assert!(Db::init_read_only(db_path)
+            .unwrap()
+            .table1
+            .get_all()
+            .unwrap()
+            .is_empty());
+
+        db.flush().unwrap();
+        let stats = db.durability_stats().unwrap();
+        assert_eq!(stats.buffered_writes, 0);
+        assert_eq!(stats.writes, 3);
+        assert_eq!(
+            Db::init_read_only(db_path)
+                .unwrap()
+                .table1
+                .get_all()
+                .unwrap()
+                .len(),
+            3
+        );
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn buffer_is_written_when_full() {
+        let db_path = &test_db();
+
+        let db = open_buffered(
+            db_path,
+            BufferPolicy {
+                max_bytes: 100,
+                max_age: Duration::from_secs(3600),
+            },
+        );
+        db.table1.insert(0, "small".to_string()).unwrap();
+        assert_eq!(log_len(db_path), HEADER_LEN);
+
+        db.table1.insert(1, "x".repeat(100)).unwrap();
+        assert!(log_len(db_path) > HEADER_LEN + 100);
+        assert_eq!(db.durability_stats().unwrap().buffered_writes, 0);
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn buffer_is_written_when_old() {
+        let db_path = &test_db();
+
+        let db = open_buffered(
+            db_path,
+            BufferPolicy {
+                max_bytes: usize::MAX,
+                max_age: Duration::from_millis(50),
+            },
+        );
+        db.table1.insert(0, "zero".to_string()).unwrap();
+        assert_eq!(log_len(db_path), HEADER_LEN);
+
+        thread::sleep(Duration::from_millis(300));
+        assert_eq!(db.durability_stats().unwrap().buffered_writes, 0);
+        assert_eq!(
+            Db::init_read_only(db_path)
+                .unwrap()
+                .table1
+                .get_all()
+                .unwrap()
+                .len(),
+            1
+        );
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn buffer_is_written_on_drop() {
+        let db_path = &test_db();
+
+        let db = open_buffered(
+            db_path,
+            BufferPolicy {
+                max_bytes: usize::MAX,
+                max_age: Duration::from_secs(3600),
+            },
+        );
+        db.table1.insert(0, "zero".to_string()).unwrap();
+        db.clone().table1.insert(1, "one".to_string()).unwrap();
+        assert_eq!(log_len(db_path), HEADER_LEN);
+        drop(db);
+
+        assert_eq!(
+            Db::init(db_path).unwrap().table1.get_all().unwrap().len(),
+            2
+        );
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+}
@@ -0,0 +1,156 @@
+#[cfg(test)]
+pub mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use hmdb::errors::Error;
+    use hmdb::log::{Reader, FORMAT_VERSION};
+    use uuid::Uuid;
+
+    use crate::tests::changed::Changed;
+    use crate::tests::schema::Db;
+
+    const SCHEMA_NAME: &str = "Db";
+    const MAGIC: [u8; 8] = [0x89, b'h', b'm', b'd', b'b', b'\r', b'\n', 0x1a];
+
+    mod schema {
+        use hmdb::schema;
+
+        schema! {
+            Db {
+                table1: <u64, String>,
+                table2: <String, Vec<u8>>
+            }
+        }
+    }
+
+    mod reformatted {
+        use hmdb::schema;
+
+        schema! {
+            Reformatted as "Db" {
+                table1: < u64 , String >,
+                table2: <String, Vec< u8 >>
+            }
+        }
+    }
+
+    mod changed {
+        use hmdb::schema;
+
+        schema! {
+            Changed as "Db" {
+                table1: <u64, String>,
+                table2: <String, Vec<u16>>
+            }
+        }
+    }
+
+    fn test_db() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("target")
+            .join(Uuid::new_v4().to_string())
+    }
+
+    #[test]
+    fn changed_schema_is_rejected() {
+        let db_path = &test_db();
+
+        let db = Db::init(db_path).unwrap();
+        db.table2.insert("one".to_string(), vec![1]).unwrap();
+        drop(db);
+        assert!(fs::read(db_path.join(SCHEMA_NAME))
+            .unwrap()
+            .starts_with(&MAGIC));
+
+        match Changed::init(db_path) {
+            Err(Error::SchemaMismatch(msg)) => assert!(msg.contains("Vec<u16>"), "{}", msg),
+            other => panic!(
+                "expected Error::SchemaMismatch, got {:?}",
+                other.map(|_| ())
+            ),
+        }
+        assert!(matches!(
+            Changed::init_read_only(db_path),
+            Err(Error::SchemaMismatch(_))
+        ));
+
+        // Only the tables count, not how the invocation is formatted
+        let db = reformatted::Reformatted::init(db_path).unwrap();
+        assert_eq!(db.table2.get(&"one".to_string()).unwrap().unwrap(), [1]);
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn newer_format_is_rejected() {
+        let db_path = &test_db();
+
+        drop(Db::init(db_path).unwrap());
+        let mut bytes = fs::read(db_path.join(SCHEMA_NAME)).unwrap();
+        bytes[8..12].copy_from_slice(&u32::MAX.to_be_bytes());
+        fs::write(db_path.join(SCHEMA_NAME), bytes).unwrap();
+
+        assert!(matches!(
+            Db::init(db_path),
+            Err(Error::UnsupportedFormat(_))
+        ));
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn torn_header_is_rewritten() {
+        let db_path = &test_db();
+
+        drop(Db::init(db_path).unwrap());
+        let header = fs::read(db_path.join(SCHEMA_NAME)).unwrap();
+        fs::write(db_path.join(SCHEMA_NAME), &header[..header.len() - 3]).unwrap();
+
+        let db = Db::init(db_path).unwrap();
+        assert!(db.incomplete_write());
+        assert_eq!(fs::read(db_path.join(SCHEMA_NAME)).unwrap(), header);
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn headerless_log_is_rewritten_on_open() {
+        let db_path = &test_db();
+        fs::create_dir_all(db_path).unwrap();
+
+        // What a log written before the header was introduced looks like, its records name
+        // their table by its position: LogItems::Single(Db::table1(TableEvent::Insert(1, "one")))
+        let mut record = vec![0; 12];
+        record.extend_from_slice(&1u64.to_le_bytes());
+        record.extend_from_slice(&3u64.to_le_bytes());
+        record.extend_from_slice(b"one");
+        let mut log = (record.len() as u32).to_be_bytes().to_vec();
+        log.extend(record);
+        fs::write(db_path.join(SCHEMA_NAME), log).unwrap();
+
+        let read_only = Db::init_read_only(db_path).unwrap();
+        assert_eq!(read_only.recovery_report().format_version, 0);
+        assert_eq!(read_only.table1.get(&1).unwrap().unwrap(), "one");
+
+        let db = Db::init(db_path).unwrap();
+        assert!(fs::read(db_path.join(SCHEMA_NAME))
+            .unwrap()
+            .starts_with(&MAGIC));
+        db.table2.insert("two".to_string(), vec![2]).unwrap();
+        drop(db);
+
+        let db = Db::init(db_path).unwrap();
+        assert_eq!(db.recovery_report().format_version, FORMAT_VERSION);
+        assert_eq!(db.table1.get(&1).unwrap().unwrap(), "one");
+        assert_eq!(db.table2.get(&"two".to_string()).unwrap().unwrap(), [2]);
+        drop(db);
+
+        assert!(matches!(
+            Changed::init(db_path),
+            Err(Error::SchemaMismatch(_))
+        ));
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+}
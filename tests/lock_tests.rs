@@ -0,0 +1,111 @@
+#[cfg(test)]
+pub mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process;
+    use std::process::{Command, Stdio};
+    use std::thread;
+    use std::time::Duration;
+
+    use hmdb::errors::Error;
+    use hmdb::log::Reader;
+    use uuid::Uuid;
+
+    use crate::tests::schema::Db;
+
+    const SCHEMA_NAME: &str = "Db";
+    const HOLDER_DB: &str = "HMDB_HOLDER_DB";
+
+    mod schema {
+        use hmdb::schema;
+
+        schema! {
+            Db {
+                table1: <u64, String>
+            }
+        }
+    }
+
+    fn test_db() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("target")
+            .join(Uuid::new_v4().to_string())
+    }
+
+    /// Run by `second_process_is_rejected` in a child process, holds the db open until killed.
+    #[test]
+    #[ignore]
+    fn holder_child() {
+        let db_path = match env::var(HOLDER_DB) {
+            Ok(db_path) => db_path,
+            Err(_) => return,
+        };
+
+        let db = Db::init(db_path).unwrap();
+        db.table1.insert(1, "one".to_string()).unwrap();
+        loop {
+            thread::sleep(Duration::from_secs(1));
+        }
+    }
+
+    #[test]
+    fn second_process_is_rejected() {
+        let db_path = &test_db();
+
+        let mut child = Command::new(env::current_exe().unwrap())
+            .args(["tests::holder_child", "--exact", "--ignored"])
+            .env(HOLDER_DB, db_path)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+
+        // The holder writes its pid once it has the lock
+        let lock_file = db_path.join(format!("{}.lock", SCHEMA_NAME));
+        let pid = child.id().to_string();
+        for _ in 0..500 {
+            if fs::read_to_string(&lock_file).unwrap_or_default() == pid {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        match Db::init(db_path) {
+            Err(Error::AlreadyOpen(_, holder)) => assert_eq!(holder, Some(child.id())),
+            other => panic!("expected Error::AlreadyOpen, got {:?}", other.map(|_| ())),
+        }
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+
+        let db = Db::init(db_path).unwrap();
+        assert_eq!(db.table1.get(&1).unwrap().unwrap(), "one");
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn same_process_refuses_a_second_handle() {
+        let db_path = &test_db();
+
+        let db1 = Db::init(db_path).unwrap();
+        match Db::init(db_path) {
+            Err(Error::AlreadyOpen(_, holder)) => assert_eq!(holder, Some(process::id())),
+            other => panic!("expected Error::AlreadyOpen, got {:?}", other.map(|_| ())),
+        }
+
+        // A clone shares the writer, so it sees what the first handle wrote
+        let db2 = db1.clone();
+        db1.table1.insert(1, "one".to_string()).unwrap();
+        drop(db1);
+        assert_eq!(db2.table1.get(&1).unwrap().unwrap(), "one");
+        db2.table1.insert(2, "two".to_string()).unwrap();
+        drop(db2);
+
+        let db = Db::init(db_path).unwrap();
+        assert_eq!(db.table1.get_all().unwrap().len(), 2);
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+}
@@ -0,0 +1,180 @@
+#[cfg(test)]
+pub mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use hmdb::errors::Error;
+    use hmdb::log::Reader;
+    use uuid::Uuid;
+
+    use crate::tests::v1::SchemaV1;
+    use crate::tests::v2::{Account, SchemaV2};
+    use crate::tests::v3::SchemaV3;
+
+    const SCHEMA_NAME: &str = "SchemaV1";
+
+    mod v1 {
+        use hmdb::schema;
+
+        schema! {
+            SchemaV1 {
+                accounts: <u64, String>,
+                files: <u64, u32>
+            }
+        }
+    }
+
+    mod v2 {
+        use hmdb::errors::Error;
+        use hmdb::migration::{convert, Migration};
+        use hmdb::schema;
+        use serde::{Deserialize, Serialize};
+
+        use super::v1::{self, SchemaV1};
+
+        #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+        pub struct Account {
+            pub name: String,
+        }
+
+        impl From<String> for Account {
+            fn from(name: String) -> Self {
+                Account { name }
+            }
+        }
+
+        schema! {
+            SchemaV2 from SchemaV1 {
+                accounts: <u64, Account>,
+                files: <u64, u32>
+            }
+        }
+
+        impl Migration for SchemaV2 {
+            type Previous = SchemaV1;
+
+            fn migrate(previous: v1::tables::SchemaV1) -> Result<tables::SchemaV2, Error> {
+                if previous.accounts.values().any(|name| name.is_empty()) {
+                    return Err(Error::MigrationFailed("accounts need a name".to_string()));
+                }
+
+                Ok(tables::SchemaV2 {
+                    accounts: convert(previous.accounts),
+                    files: previous.files,
+                })
+            }
+        }
+    }
+
+    mod v3 {
+        use hmdb::errors::Error;
+        use hmdb::migration::{convert, Migration};
+        use hmdb::schema;
+
+        use super::v2::{self, Account, SchemaV2};
+
+        schema! {
+            SchemaV3 from SchemaV2 {
+                accounts: <u64, Account>,
+                files: <u64, u64>,
+                sizes: <u64, u64>
+            }
+        }
+
+        impl Migration for SchemaV3 {
+            type Previous = SchemaV2;
+
+            fn migrate(previous: v2::tables::SchemaV2) -> Result<tables::SchemaV3, Error> {
+                Ok(tables::SchemaV3 {
+                    accounts: previous.accounts,
+                    files: convert(previous.files),
+                    ..Default::default()
+                })
+            }
+        }
+    }
+
+    fn test_db() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("target")
+            .join(Uuid::new_v4().to_string())
+    }
+
+    fn populate(db_path: &PathBuf, name: &str) {
+        let db = SchemaV1::init(db_path).unwrap();
+        db.accounts.insert(1, name.to_string()).unwrap();
+        db.files.insert(1, 7).unwrap();
+    }
+
+    fn account(name: &str) -> Account {
+        Account {
+            name: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn migrations_are_chained() {
+        let db_path = &test_db();
+        populate(db_path, "parth");
+
+        let db = SchemaV3::init(db_path).unwrap();
+        assert_eq!(db.recovery_report().schema_version, 1);
+        assert_eq!(db.accounts.get(&1).unwrap().unwrap(), account("parth"));
+        assert_eq!(db.files.get(&1).unwrap().unwrap(), 7u64);
+        db.sizes.insert(1, 1024).unwrap();
+        drop(db);
+
+        // The backup is gone once the log was rewritten
+        let entries = fs::read_dir(db_path).unwrap().count();
+        assert!(!db_path.join(SCHEMA_NAME).with_extension("v1").exists());
+
+        let db = SchemaV3::init(db_path).unwrap();
+        assert_eq!(db.recovery_report().schema_version, 3);
+        assert_eq!(db.sizes.get(&1).unwrap().unwrap(), 1024);
+        assert_eq!(fs::read_dir(db_path).unwrap().count(), entries);
+        drop(db);
+
+        // Older versions can't read what newer ones wrote
+        assert!(matches!(
+            SchemaV1::init(db_path),
+            Err(Error::SchemaMismatch(_))
+        ));
+        assert!(matches!(
+            SchemaV2::init_read_only(db_path),
+            Err(Error::SchemaMismatch(_))
+        ));
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn failed_migration_leaves_the_log() {
+        let db_path = &test_db();
+        populate(db_path, "");
+        let log = fs::read(db_path.join(SCHEMA_NAME)).unwrap();
+
+        assert!(matches!(
+            SchemaV3::init(db_path),
+            Err(Error::MigrationFailed(_))
+        ));
+        assert_eq!(fs::read(db_path.join(SCHEMA_NAME)).unwrap(), log);
+
+        let db = SchemaV1::init(db_path).unwrap();
+        assert_eq!(db.accounts.get(&1).unwrap().unwrap(), "");
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn read_only_migrates_in_memory() {
+        let db_path = &test_db();
+        populate(db_path, "parth");
+        let log = fs::read(db_path.join(SCHEMA_NAME)).unwrap();
+
+        let db = SchemaV2::init_read_only(db_path).unwrap();
+        assert_eq!(db.accounts.get(&1).unwrap().unwrap(), account("parth"));
+        assert_eq!(fs::read(db_path.join(SCHEMA_NAME)).unwrap(), log);
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+}
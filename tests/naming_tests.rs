@@ -0,0 +1,147 @@
+#[cfg(test)]
+pub mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use hmdb::log::{LogCompacter, Reader};
+    use uuid::Uuid;
+
+    use crate::tests::files::Files;
+    use crate::tests::named::Renamed;
+    use crate::tests::schema::Db;
+    use crate::tests::users::Users;
+
+    const LEGACY_NAME: &str = "naming_tests__tests__schema__Db";
+
+    mod schema {
+        use hmdb::schema;
+
+        schema! {
+            Db {
+                table1: <u64, String>
+            }
+        }
+    }
+
+    mod named {
+        use hmdb::schema;
+
+        schema! {
+            Renamed as "Db" {
+                table1: <u64, String>
+            }
+        }
+    }
+
+    mod users {
+        use hmdb::schema;
+
+        schema! {
+            Users as "app.users" {
+                table1: <u64, String>
+            }
+        }
+    }
+
+    mod files {
+        use hmdb::schema;
+
+        schema! {
+            Files as "app.files" {
+                table1: <u64, String>
+            }
+        }
+    }
+
+    fn test_db() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("target")
+            .join(Uuid::new_v4().to_string())
+    }
+
+    #[test]
+    fn log_is_named_after_the_schema() {
+        let db_path = &test_db();
+
+        let db = Db::init(db_path).unwrap();
+        db.table1.insert(1, "one".to_string()).unwrap();
+        assert!(db_path.join("Db").exists());
+        drop(db);
+
+        // A schema that names its log explicitly opens the same file
+        let db = Renamed::init(db_path).unwrap();
+        assert_eq!(db.table1.get(&1).unwrap().unwrap(), "one");
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn dotted_names_keep_their_files_apart() {
+        let db_path = &test_db();
+
+        // Both hold their own lock
+        let users = Users::init(db_path).unwrap();
+        let files = Files::init(db_path).unwrap();
+        users.table1.insert(1, "user".to_string()).unwrap();
+        files.table1.insert(2, "file".to_string()).unwrap();
+        files.compact_log().unwrap();
+        users.compact_log().unwrap();
+        drop(users);
+        drop(files);
+
+        for name in ["app.users", "app.files"] {
+            for file in ["lock", "manifest", "snapshot.1"] {
+                assert!(db_path.join(format!("{}.{}", name, file)).exists());
+            }
+        }
+        let files = Files::init(db_path).unwrap();
+        assert_eq!(files.table1.get_all().unwrap().len(), 1);
+        assert_eq!(files.table1.get(&2).unwrap().unwrap(), "file");
+        let users = Users::init(db_path).unwrap();
+        assert_eq!(users.table1.get_all().unwrap().len(), 1);
+        assert_eq!(users.table1.get(&1).unwrap().unwrap(), "user");
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn legacy_log_is_migrated() {
+        let db_path = &test_db();
+
+        let db = Db::init(db_path).unwrap();
+        db.table1.insert(1, "one".to_string()).unwrap();
+        drop(db);
+        fs::rename(db_path.join("Db"), db_path.join(LEGACY_NAME)).unwrap();
+
+        let db = Db::init_read_only(db_path).unwrap();
+        assert_eq!(db.table1.get(&1).unwrap().unwrap(), "one");
+        assert!(!db_path.join("Db").exists());
+
+        let db = Db::init(db_path).unwrap();
+        assert_eq!(db.table1.get(&1).unwrap().unwrap(), "one");
+        assert!(db_path.join("Db").exists());
+        assert!(!db_path.join(LEGACY_NAME).exists());
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn legacy_log_is_ignored_next_to_a_current_one() {
+        let db_path = &test_db();
+
+        let db = Db::init(db_path).unwrap();
+        db.table1.insert(1, "one".to_string()).unwrap();
+        drop(db);
+        fs::copy(db_path.join("Db"), db_path.join(LEGACY_NAME)).unwrap();
+
+        let db = Db::init(db_path).unwrap();
+        db.table1.insert(2, "two".to_string()).unwrap();
+        drop(db);
+
+        let db = Db::init(db_path).unwrap();
+        assert_eq!(db.table1.get_all().unwrap().len(), 2);
+        assert!(db_path.join(LEGACY_NAME).exists());
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+}
@@ -0,0 +1,170 @@
+#[cfg(test)]
+pub mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use hmdb::compaction::CompactionPolicy;
+    use hmdb::durability::{BufferPolicy, Durability, Durable};
+    use hmdb::errors::Error;
+    use hmdb::lock::Locking;
+    use hmdb::log::Reader;
+    use hmdb::options::Options;
+    use hmdb::recovery::RecoveryPolicy;
+    use uuid::Uuid;
+
+    use crate::tests::schema::Db;
+
+    mod schema {
+        use hmdb::schema;
+
+        schema! {
+            Db {
+                table1: <u64, String>
+            }
+        }
+    }
+
+    fn test_db() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("target")
+            .join(Uuid::new_v4().to_string())
+    }
+
+    #[test]
+    fn builder_opens_with_its_options() {
+        let db_path = &test_db();
+
+        let db = Db::options()
+            .sync(Durability::EveryCommit)
+            .open(db_path)
+            .unwrap();
+        db.table1.insert(1, "one".to_string()).unwrap();
+        let stats = db.durability_stats().unwrap();
+        assert_eq!(stats.synced_writes, 1);
+        drop(db);
+
+        let db = Db::options()
+            .buffer(BufferPolicy::default())
+            .open(db_path)
+            .unwrap();
+        db.table1.insert(2, "two".to_string()).unwrap();
+        assert_eq!(db.durability_stats().unwrap().buffered_writes, 1);
+        drop(db);
+
+        let db = Db::options().open_read_only(db_path).unwrap();
+        assert_eq!(db.table1.get_all().unwrap().len(), 2);
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn contradicting_options_are_rejected() {
+        let db_path = &test_db();
+
+        let invalid = [
+            Db::options()
+                .sync(Durability::GroupCommit)
+                .buffer(BufferPolicy::default()),
+            Db::options()
+                .locking(Locking::Shared)
+                .buffer(BufferPolicy::default()),
+            Db::options().buffer(BufferPolicy {
+                max_bytes: 1024,
+                max_age: Duration::ZERO,
+            }),
+            Db::options().sync(Durability::Interval(Duration::ZERO)),
+            Db::options().compaction(CompactionPolicy {
+                ratio: Some(1.0),
+                ..Default::default()
+            }),
+            Db::options().segment_bytes(0),
+            Db::options().max_record_bytes(0),
+            Db::options().log_name(""),
+            Db::options().log_name("../Db"),
+        ];
+        for options in invalid {
+            assert!(matches!(
+                options.open(db_path),
+                Err(Error::InvalidOptions(_))
+            ));
+        }
+        assert!(!db_path.exists());
+
+        // Options built by hand are checked too
+        let options = Options {
+            segment_bytes: Some(0),
+            ..Default::default()
+        };
+        assert!(matches!(
+            Db::init_with(db_path, options.clone()),
+            Err(Error::InvalidOptions(_))
+        ));
+        assert!(matches!(
+            Db::init_read_only_with(db_path, options),
+            Err(Error::InvalidOptions(_))
+        ));
+        assert!(!db_path.exists());
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn log_name_keeps_logs_apart() {
+        let db_path = &test_db();
+
+        let first = Db::options().log_name("first").open(db_path).unwrap();
+        let second = Db::options().log_name("second").open(db_path).unwrap();
+        first.table1.insert(1, "one".to_string()).unwrap();
+        second.table1.insert(2, "two".to_string()).unwrap();
+        drop(first);
+        drop(second);
+        assert!(db_path.join("first").exists());
+        assert!(db_path.join("second.lock").exists());
+        assert!(!db_path.join("Db").exists());
+
+        let first = Db::options()
+            .log_name("first")
+            .open_read_only(db_path)
+            .unwrap();
+        assert_eq!(first.table1.get_all().unwrap().len(), 1);
+        assert_eq!(first.table1.get(&1).unwrap().unwrap(), "one");
+        let second = Db::options().log_name("second").open(db_path).unwrap();
+        assert_eq!(second.table1.get_all().unwrap().len(), 1);
+        assert_eq!(second.table1.get(&2).unwrap().unwrap(), "two");
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn read_only_uses_the_recovery_policy() {
+        let db_path = &test_db();
+
+        let db = Db::init(db_path).unwrap();
+        db.table1.insert(1, "one".to_string()).unwrap();
+        let first = fs::metadata(db_path.join("Db")).unwrap().len() as usize;
+        db.table1.insert(2, "two".to_string()).unwrap();
+        drop(db);
+
+        // Damage the first record, the second one is still readable
+        let mut bytes = fs::read(db_path.join("Db")).unwrap();
+        bytes[first - 1] ^= 0xff;
+        fs::write(db_path.join("Db"), bytes).unwrap();
+
+        assert!(matches!(
+            Db::init_read_only(db_path),
+            Err(Error::CorruptLog(_))
+        ));
+        let db = Db::options()
+            .recovery(RecoveryPolicy {
+                salvage: true,
+                ..Default::default()
+            })
+            .open_read_only(db_path)
+            .unwrap();
+        assert_eq!(db.recovery_report().skipped.len(), 1);
+        assert_eq!(db.table1.get(&2).unwrap().unwrap(), "two");
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+}
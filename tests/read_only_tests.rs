@@ -0,0 +1,118 @@
+#[cfg(test)]
+pub mod tests {
+    use std::fs;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use std::path::{Path, PathBuf};
+
+    use hmdb::errors::Error;
+    use hmdb::log::{LogCompacter, Reader};
+    use uuid::Uuid;
+
+    use crate::tests::schema::Db;
+
+    const SCHEMA_NAME: &str = "Db";
+
+    mod schema {
+        use hmdb::schema;
+
+        schema! {
+            Db {
+                table1: <u64, String>,
+                table2: <String, u64>
+            }
+        }
+    }
+
+    fn test_db() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("target")
+            .join(Uuid::new_v4().to_string())
+    }
+
+    fn dir_listing(db_path: &Path) -> Vec<(PathBuf, u64)> {
+        let mut listing: Vec<_> = fs::read_dir(db_path)
+            .unwrap()
+            .map(|entry| {
+                let entry = entry.unwrap();
+                (entry.path(), entry.metadata().unwrap().len())
+            })
+            .collect();
+        listing.sort();
+        listing
+    }
+
+    #[test]
+    fn missing_db_is_not_created() {
+        let db_path = &test_db();
+
+        match Db::init_read_only(db_path) {
+            Err(Error::OsError(_, err)) => assert_eq!(err.kind(), std::io::ErrorKind::NotFound),
+            other => panic!("expected Error::OsError, got {:?}", other.map(|_| ())),
+        }
+        assert!(!db_path.exists());
+    }
+
+    #[test]
+    fn follows_a_db_another_handle_is_writing() {
+        let db_path = &test_db();
+
+        let db = Db::init(db_path).unwrap();
+        db.table1.insert(1, "one".to_string()).unwrap();
+        db.table2.insert("two".to_string(), 2).unwrap();
+
+        let before = dir_listing(db_path);
+        let reader = Db::init_read_only(db_path).unwrap();
+        assert_eq!(dir_listing(db_path), before);
+        assert!(!reader.incomplete_write());
+        assert_eq!(reader.table1.get(&1).unwrap().unwrap(), "one");
+        assert_eq!(reader.table2.get(&"two".to_string()).unwrap().unwrap(), 2);
+
+        db.table1.insert(3, "three".to_string()).unwrap();
+        assert!(!reader.table1.exists(&3).unwrap());
+        reader.refresh().unwrap();
+        assert_eq!(reader.table1.get(&3).unwrap().unwrap(), "three");
+
+        db.table1.delete(1).unwrap();
+        db.compact_log().unwrap();
+        db.table1.insert(4, "four".to_string()).unwrap();
+        reader.refresh().unwrap();
+        assert_eq!(
+            reader.table1.get_all().unwrap(),
+            db.table1.get_all().unwrap()
+        );
+        assert_eq!(
+            reader.table2.get_all().unwrap(),
+            db.table2.get_all().unwrap()
+        );
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn torn_tail_is_left_in_place() {
+        let db_path = &test_db();
+
+        let db = Db::init(db_path).unwrap();
+        db.table1.insert(1, "one".to_string()).unwrap();
+        drop(db);
+
+        let log_path = db_path.join(SCHEMA_NAME);
+        let mut log = OpenOptions::new().append(true).open(&log_path).unwrap();
+        log.write_all(&[0xdb, 0x1e, 0x5e, 0xc0, 0, 0]).unwrap();
+        let len = fs::metadata(&log_path).unwrap().len();
+
+        let reader = Db::init_read_only(db_path).unwrap();
+        assert!(reader.incomplete_write());
+        assert_eq!(reader.table1.get(&1).unwrap().unwrap(), "one");
+        assert_eq!(fs::metadata(&log_path).unwrap().len(), len);
+
+        // Once a writer repairs the log, the reader picks up from where the torn write began
+        let db = Db::init(db_path).unwrap();
+        db.table1.insert(2, "two".to_string()).unwrap();
+        reader.refresh().unwrap();
+        assert_eq!(reader.table1.get(&2).unwrap().unwrap(), "two");
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+}
@@ -0,0 +1,142 @@
+#[cfg(test)]
+pub mod tests {
+    use std::fs;
+    use std::fs::File;
+    use std::path::{Path, PathBuf};
+
+    use hmdb::errors::Error;
+    use hmdb::log::{read_files, LogCompacter, Reader};
+    use hmdb::recovery::RecoveryPolicy;
+    use hmdb::transaction::Transaction;
+    use uuid::Uuid;
+
+    use crate::tests::db::Db;
+
+    mod db {
+        use hmdb::schema;
+
+        schema! {
+            Db {
+                blobs: <u64, Vec<u8>>
+            }
+        }
+    }
+
+    const MAX_RECORD_BYTES: u64 = 10_000;
+
+    fn test_db() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("target")
+            .join(Uuid::new_v4().to_string())
+    }
+
+    fn open(db_path: &Path) -> Db {
+        Db::options()
+            .max_record_bytes(MAX_RECORD_BYTES)
+            .open(db_path)
+            .unwrap()
+    }
+
+    #[test]
+    fn records_over_the_limit_are_refused() {
+        let db_path = &test_db();
+        let db = open(db_path);
+        db.blobs.insert(1, vec![1; 100]).unwrap();
+        let log_bytes = db.log_stats().unwrap().log_bytes();
+
+        let result = db.blobs.insert(2, vec![2; 20_000]);
+        assert!(matches!(result, Err(Error::RecordTooLarge(_))));
+        assert_eq!(db.blobs.get(&2).unwrap(), None);
+        let result = db.transaction(|tx| {
+            for key in 3..10 {
+                tx.blobs.insert(key, vec![3; 2_000]);
+            }
+        });
+        assert!(matches!(result, Err(Error::RecordTooLarge(_))));
+        assert_eq!(db.log_stats().unwrap().log_bytes(), log_bytes);
+        drop(db);
+
+        let db = open(db_path);
+        assert_eq!(db.blobs.get_all().unwrap().len(), 1);
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn a_refused_transaction_leaves_the_tables() {
+        let db_path = &test_db();
+        let db = open(db_path);
+        db.blobs.insert(1, vec![1; 100]).unwrap();
+        db.blobs.insert(2, vec![2; 100]).unwrap();
+
+        let result = db.transaction(|tx| {
+            tx.blobs.delete(2);
+            tx.blobs.insert(3, vec![3; 100]);
+            tx.blobs.insert(1, vec![4; 20_000]);
+        });
+        assert!(matches!(result, Err(Error::RecordTooLarge(_))));
+        assert_eq!(db.blobs.get(&1).unwrap().unwrap(), vec![1; 100]);
+        assert_eq!(db.blobs.get(&2).unwrap().unwrap(), vec![2; 100]);
+        assert_eq!(db.blobs.get(&3).unwrap(), None);
+
+        let result = db.transaction(|tx| {
+            tx.blobs.clear();
+            tx.blobs.insert(4, vec![4; 20_000]);
+        });
+        assert!(matches!(result, Err(Error::RecordTooLarge(_))));
+        let blobs = db.blobs.get_all().unwrap();
+        drop(db);
+
+        assert_eq!(blobs.len(), 2);
+        let db = open(db_path);
+        assert_eq!(db.blobs.get_all().unwrap(), blobs);
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn snapshots_are_written_in_chunks() {
+        let db_path = &test_db();
+        let db = open(db_path);
+        for key in 0..100 {
+            db.blobs.insert(key, vec![key as u8; 1_000]).unwrap();
+        }
+        db.compact_log().unwrap();
+        drop(db);
+
+        // The live segment after the snapshot is empty
+        let path = db_path.join("Db");
+        let (_, files) = read_files(File::open(&path).unwrap(), &path, false).unwrap();
+        assert!(files.sealed[0].len() > 100_000);
+        let mut entries = 0;
+        let policy = RecoveryPolicy::default();
+        let report = Db::parse_files(&files, &policy, None, None, 1, |_| entries += 1).unwrap();
+        assert_eq!(entries, 100);
+        assert!(report.records >= 10, "{:?}", report);
+
+        let db = open(db_path);
+        assert!(db.recovery_report().is_clean());
+        for key in 0..100 {
+            assert_eq!(db.blobs.get(&key).unwrap().unwrap(), vec![key as u8; 1_000]);
+        }
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn an_entry_over_the_limit_fails_the_compaction() {
+        let db_path = &test_db();
+        let db = Db::init(db_path).unwrap();
+        db.blobs.insert(1, vec![1; 20_000]).unwrap();
+        drop(db);
+
+        let db = open(db_path);
+        assert!(matches!(db.compact_log(), Err(Error::RecordTooLarge(_))));
+        drop(db);
+
+        let db = open(db_path);
+        assert_eq!(db.blobs.get(&1).unwrap().unwrap(), vec![1; 20_000]);
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+}
@@ -0,0 +1,293 @@
+#[cfg(test)]
+pub mod tests {
+    use std::fs;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use std::path::{Path, PathBuf};
+
+    use hmdb::compression::Compression;
+    use hmdb::errors::Error;
+    use hmdb::log::{LogCompacter, Reader};
+    use hmdb::recovery::RecoveryPolicy;
+    use uuid::Uuid;
+
+    use crate::tests::schema::Db;
+
+    const SCHEMA_NAME: &str = "Db";
+
+    mod schema {
+        use hmdb::schema;
+
+        schema! {
+            Db {
+                table1: <u8, String>,
+                table2: <String, u64>
+            }
+        }
+    }
+
+    fn test_db() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("target")
+            .join(Uuid::new_v4().to_string())
+    }
+
+    fn tear_log(db_path: &Path, garbage: &[u8]) {
+        OpenOptions::new()
+            .append(true)
+            .open(db_path.join(SCHEMA_NAME))
+            .unwrap()
+            .write_all(garbage)
+            .unwrap();
+    }
+
+    fn log_len(db_path: &Path) -> u64 {
+        fs::metadata(db_path.join(SCHEMA_NAME)).unwrap().len()
+    }
+
+    #[test]
+    fn clean_log_is_untouched() {
+        let db_path = &test_db();
+
+        let db = Db::init(db_path).unwrap();
+        db.table1.insert(1, "one".to_string()).unwrap();
+        let len = log_len(db_path);
+
+        drop(db);
+        let db = Db::init(db_path).unwrap();
+        assert!(db.recovery_report().is_clean());
+        assert!(!db.incomplete_write());
+        assert_eq!(db.recovery_report().valid_bytes, len);
+        assert_eq!(log_len(db_path), len);
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn torn_length_prefix_is_truncated() {
+        let db_path = &test_db();
+
+        let db = Db::init(db_path).unwrap();
+        db.table1.insert(1, "one".to_string()).unwrap();
+        let len = log_len(db_path);
+        tear_log(db_path, &[0, 0]);
+
+        drop(db);
+        let db = Db::init(db_path).unwrap();
+        assert!(db.incomplete_write());
+        assert_eq!(db.recovery_report().truncated_bytes, 2);
+        assert_eq!(db.recovery_report().valid_bytes, len);
+        assert_eq!(log_len(db_path), len);
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn writes_after_torn_tail_survive() {
+        let db_path = &test_db();
+
+        let db = Db::init(db_path).unwrap();
+        db.table1.insert(1, "one".to_string()).unwrap();
+        tear_log(db_path, &[0, 0, 0, 200, 1, 2, 3]);
+
+        drop(db);
+        let db = Db::init(db_path).unwrap();
+        assert_eq!(db.recovery_report().truncated_bytes, 7);
+        db.table2.insert("two".to_string(), 2).unwrap();
+
+        drop(db);
+        let db = Db::init(db_path).unwrap();
+        assert!(db.recovery_report().is_clean());
+        assert_eq!(db.table1.get(&1).unwrap().unwrap(), "one");
+        assert_eq!(db.table2.get(&"two".to_string()).unwrap().unwrap(), 2);
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn torn_tail_can_be_preserved() {
+        let db_path = &test_db();
+
+        let db = Db::init(db_path).unwrap();
+        db.table1.insert(1, "one".to_string()).unwrap();
+        tear_log(db_path, &[0, 0, 0, 200, 1, 2, 3]);
+
+        let policy = RecoveryPolicy {
+            preserve_torn_tail: true,
+            ..Default::default()
+        };
+        drop(db);
+        let db = Db::init_with_recovery(db_path, policy).unwrap();
+        let torn_tail = db.recovery_report().torn_tail.clone().unwrap();
+        assert_eq!(fs::read(torn_tail).unwrap(), vec![0, 0, 0, 200, 1, 2, 3]);
+        assert_eq!(db.table1.get(&1).unwrap().unwrap(), "one");
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    fn write_three_corrupt_second(db_path: &Path) {
+        let db = Db::init(db_path).unwrap();
+        db.table1.insert(1, "one".to_string()).unwrap();
+        let first = log_len(db_path) as usize;
+        db.table1.insert(2, "two".to_string()).unwrap();
+        db.table1.insert(3, "three".to_string()).unwrap();
+
+        let mut bytes = fs::read(db_path.join(SCHEMA_NAME)).unwrap();
+        bytes[first + 14] ^= 0xff;
+        fs::write(db_path.join(SCHEMA_NAME), bytes).unwrap();
+    }
+
+    #[test]
+    fn corrupt_record_fails_strict_open() {
+        let db_path = &test_db();
+
+        write_three_corrupt_second(db_path);
+        assert!(matches!(Db::init(db_path), Err(Error::CorruptLog(_))));
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn salvage_skips_corrupt_record() {
+        let db_path = &test_db();
+
+        write_three_corrupt_second(db_path);
+        let len = log_len(db_path);
+
+        let policy = RecoveryPolicy {
+            salvage: true,
+            ..Default::default()
+        };
+        let db = Db::init_with_recovery(db_path, policy).unwrap();
+        let report = db.recovery_report();
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(report.truncated_bytes, 0);
+        assert_eq!(report.valid_bytes, len);
+        assert_eq!(db.table1.get(&1).unwrap().unwrap(), "one");
+        assert_eq!(db.table1.get(&2).unwrap(), None);
+        assert_eq!(db.table1.get(&3).unwrap().unwrap(), "three");
+
+        // Compacting accepts the partial data, after which a strict open succeeds again
+        db.compact_log().unwrap();
+        drop(db);
+        let db = Db::init(db_path).unwrap();
+        assert!(db.recovery_report().is_clean());
+        assert_eq!(db.table1.get_all().unwrap().len(), 2);
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn salvaged_log_is_kept_when_it_is_rewritten() {
+        let db_path = &test_db();
+
+        write_three_corrupt_second(db_path);
+        let damaged = fs::read(db_path.join(SCHEMA_NAME)).unwrap();
+
+        // Compressing the log rewrites it, without the damaged record
+        let db = Db::options()
+            .recovery(RecoveryPolicy {
+                salvage: true,
+                ..Default::default()
+            })
+            .compression(Compression::default())
+            .open(db_path)
+            .unwrap();
+        let copy = db_path.join(format!("{}.format-1", SCHEMA_NAME));
+        assert_eq!(db.recovery_report().kept_copies, vec![copy.clone()]);
+        assert_eq!(fs::read(&copy).unwrap(), damaged);
+        drop(db);
+
+        // A clean log is rewritten without a copy
+        fs::remove_file(&copy).unwrap();
+        let db = Db::init(db_path).unwrap();
+        assert!(db.recovery_report().kept_copies.is_empty());
+        assert!(!copy.exists());
+        assert_eq!(db.table1.get_all().unwrap().len(), 2);
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn corrupt_last_record_is_a_torn_tail() {
+        let db_path = &test_db();
+
+        let db = Db::init(db_path).unwrap();
+        db.table1.insert(1, "one".to_string()).unwrap();
+        let first = log_len(db_path);
+        db.table1.insert(2, "two".to_string()).unwrap();
+
+        let mut bytes = fs::read(db_path.join(SCHEMA_NAME)).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        fs::write(db_path.join(SCHEMA_NAME), bytes).unwrap();
+
+        drop(db);
+        let db = Db::init(db_path).unwrap();
+        assert_eq!(db.recovery_report().valid_bytes, first);
+        assert_eq!(db.table1.get(&2).unwrap(), None);
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn legacy_records_are_readable() {
+        let db_path = &test_db();
+        fs::create_dir_all(db_path).unwrap();
+
+        // LogItems::Single(Db::table1(TableEvent::Insert(1, "one"))) in the unframed format
+        let mut record = vec![0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+        record.extend_from_slice(&3u64.to_le_bytes());
+        record.extend_from_slice(b"one");
+        let mut log = (record.len() as u32).to_be_bytes().to_vec();
+        log.extend(record);
+        fs::write(db_path.join(SCHEMA_NAME), log).unwrap();
+
+        let db = Db::init(db_path).unwrap();
+        assert!(db.recovery_report().is_clean());
+        assert_eq!(db.table1.get(&1).unwrap().unwrap(), "one");
+        db.table1.insert(2, "two".to_string()).unwrap();
+
+        drop(db);
+        let db = Db::init(db_path).unwrap();
+        assert_eq!(db.table1.get_all().unwrap().len(), 2);
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn unframed_records_are_damaged_in_a_framed_log() {
+        let db_path = &test_db();
+
+        let db = Db::init(db_path).unwrap();
+        db.table1.insert(1, "one".to_string()).unwrap();
+        let first = log_len(db_path) as usize;
+        db.table1.insert(3, "three".to_string()).unwrap();
+        drop(db);
+
+        // LogItems::Single(Db::table1(TableEvent::Insert(2, "two"))), only prefixed by its size
+        let mut record = vec![0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2];
+        record.extend_from_slice(&3u64.to_le_bytes());
+        record.extend_from_slice(b"two");
+        let mut unframed = (record.len() as u32).to_be_bytes().to_vec();
+        unframed.extend(record);
+        let mut bytes = fs::read(db_path.join(SCHEMA_NAME)).unwrap();
+        bytes.splice(first..first, unframed.iter().copied());
+        fs::write(db_path.join(SCHEMA_NAME), bytes).unwrap();
+
+        assert!(matches!(Db::init(db_path), Err(Error::CorruptLog(_))));
+        let policy = RecoveryPolicy {
+            salvage: true,
+            ..Default::default()
+        };
+        let db = Db::init_with_recovery(db_path, policy).unwrap();
+        assert_eq!(
+            db.recovery_report().skipped,
+            vec![first as u64..(first + unframed.len()) as u64]
+        );
+        assert_eq!(db.table1.get(&2).unwrap(), None);
+        assert_eq!(db.table1.get(&3).unwrap().unwrap(), "three");
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+}
@@ -0,0 +1,217 @@
+#[cfg(test)]
+pub mod tests {
+    use std::fs;
+    use std::fs::File;
+    use std::path::{Path, PathBuf};
+    use std::sync::{Arc, Mutex};
+
+    use hmdb::log::{read_files, LogCompacter, LogFiles, Reader};
+    use hmdb::recovery::{RecoveryPolicy, ReplayProgress, PROGRESS_BYTES};
+    use hmdb::transaction::Transaction;
+    use uuid::Uuid;
+
+    use crate::tests::db::Db;
+
+    mod db {
+        use hmdb::schema;
+
+        schema! {
+            Db {
+                blobs: <u64, Vec<u8>>,
+                names: <String, u64>
+            }
+        }
+    }
+
+    const SCHEMA_NAME: &str = "Db";
+
+    fn test_db() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("target")
+            .join(Uuid::new_v4().to_string())
+    }
+
+    /// Bytes that don't compress, so that the record holding them is about as large.
+    fn blob(seed: u64, len: usize) -> Vec<u8> {
+        let mut state = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+        (0..len)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                (state >> 56) as u8
+            })
+            .collect()
+    }
+
+    /// Small records between ones larger than a read, so that records straddle reads.
+    fn blob_len(key: u64) -> usize {
+        match key % 3 {
+            0 => 150_000,
+            _ => 10 + key as usize,
+        }
+    }
+
+    fn log_files(db_path: &Path) -> LogFiles {
+        let path = db_path.join(SCHEMA_NAME);
+        read_files(File::open(&path).unwrap(), &path, false)
+            .unwrap()
+            .1
+    }
+
+    fn write_blobs(db_path: &Path) {
+        let db = Db::init(db_path).unwrap();
+        for key in 0..20 {
+            db.blobs.insert(key, blob(key, blob_len(key))).unwrap();
+        }
+    }
+
+    #[test]
+    fn records_larger_than_a_read_are_replayed() {
+        let db_path = &test_db();
+        write_blobs(db_path);
+
+        let db = Db::init(db_path).unwrap();
+        assert!(db.recovery_report().is_clean());
+        for key in 0..20 {
+            assert_eq!(
+                db.blobs.get(&key).unwrap().unwrap(),
+                blob(key, blob_len(key))
+            );
+        }
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn salvage_skips_a_damaged_record_larger_than_a_read() {
+        let db_path = &test_db();
+        let db = Db::init(db_path).unwrap();
+        db.blobs.insert(1, blob(1, 150_000)).unwrap();
+        let first = fs::metadata(db_path.join(SCHEMA_NAME)).unwrap().len();
+        db.blobs.insert(2, blob(2, 150_000)).unwrap();
+        let second = fs::metadata(db_path.join(SCHEMA_NAME)).unwrap().len();
+        db.blobs.insert(3, blob(3, 150_000)).unwrap();
+        drop(db);
+
+        let mut bytes = fs::read(db_path.join(SCHEMA_NAME)).unwrap();
+        bytes[first as usize + 100_000] ^= 0xff;
+        fs::write(db_path.join(SCHEMA_NAME), bytes).unwrap();
+
+        for threads in [1, 4] {
+            let db = Db::options()
+                .recovery(RecoveryPolicy {
+                    salvage: true,
+                    ..Default::default()
+                })
+                .replay_threads(threads)
+                .open(db_path)
+                .unwrap();
+            assert_eq!(db.recovery_report().skipped, vec![first..second]);
+            assert_eq!(db.blobs.get(&1).unwrap().unwrap(), blob(1, 150_000));
+            assert_eq!(db.blobs.get(&2).unwrap(), None);
+            assert_eq!(db.blobs.get(&3).unwrap().unwrap(), blob(3, 150_000));
+        }
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn replay_on_threads_matches_one_thread() {
+        let db_path = &test_db();
+        let db = Db::options().segment_bytes(1 << 20).open(db_path).unwrap();
+        for key in 0..4000 {
+            db.blobs.insert(key % 1000, blob(key, 500)).unwrap();
+            db.names.insert(format!("name {}", key % 700), key).unwrap();
+            if key % 7 == 0 {
+                db.blobs.delete(key / 2).unwrap();
+                db.names.delete(format!("name {}", key / 3)).unwrap();
+            }
+            if key == 1500 {
+                db.compact_log().unwrap();
+            }
+            if key == 2500 {
+                db.transaction(|tx| tx.names.clear()).unwrap();
+            }
+        }
+        let blobs = db.blobs.get_all().unwrap();
+        let names = db.names.get_all().unwrap();
+        drop(db);
+
+        for threads in [1, 2, 8] {
+            let db = Db::options().replay_threads(threads).open(db_path).unwrap();
+            assert!(db.recovery_report().is_clean());
+            assert_eq!(db.blobs.get_all().unwrap(), blobs);
+            assert_eq!(db.names.get_all().unwrap(), names);
+        }
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn report_and_progress_follow_the_replay() {
+        let db_path = &test_db();
+        let db = Db::options()
+            .segment_bytes(1_500_000)
+            .open(db_path)
+            .unwrap();
+        for key in 0..40 {
+            db.blobs.insert(key, blob(key, blob_len(key))).unwrap();
+        }
+        let log_bytes = db.log_stats().unwrap().log_bytes();
+        drop(db);
+
+        for threads in [1, 4] {
+            let seen = Arc::new(Mutex::new(vec![]));
+            let progress = seen.clone();
+            let db = Db::options()
+                .replay_threads(threads)
+                .progress(move |at: ReplayProgress| progress.lock().unwrap().push(at))
+                .open(db_path)
+                .unwrap();
+            let report = db.recovery_report();
+            assert_eq!(report.records, 40);
+            assert_eq!(report.bytes_read, log_bytes);
+            assert_eq!(
+                report.table_entries,
+                vec![("blobs".to_string(), 40), ("names".to_string(), 0)]
+            );
+            assert!(!report.elapsed.is_zero());
+
+            let seen = seen.lock().unwrap();
+            // Every `PROGRESS_BYTES`, and at the end of both files
+            assert!(seen.len() >= 3, "{:?}", seen);
+            assert!(seen[0].bytes_read < PROGRESS_BYTES * 2);
+            for (before, after) in seen.iter().zip(seen.iter().skip(1)) {
+                assert!(before.bytes_read <= after.bytes_read);
+                assert!(before.records <= after.records);
+            }
+            let last = seen.last().unwrap();
+            assert_eq!(last.bytes_read, log_bytes);
+            assert_eq!(last.total_bytes, log_bytes);
+            assert_eq!(last.records, 40);
+        }
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn parse_files_hands_out_entries_as_they_are_read() {
+        let db_path = &test_db();
+        write_blobs(db_path);
+
+        let mut entries = vec![];
+        let policy = RecoveryPolicy::default();
+        let report = Db::parse_files(&log_files(db_path), &policy, None, None, 1, |entry| {
+            entries.push(entry)
+        })
+        .unwrap();
+        assert_eq!(entries.len(), 20);
+        assert_eq!(
+            report.valid_bytes,
+            fs::metadata(db_path.join(SCHEMA_NAME)).unwrap().len()
+        );
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+}
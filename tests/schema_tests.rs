@@ -54,11 +54,13 @@ pub mod schema {
 
         let db1 = Schema::init(db_path).unwrap();
         db1.word_counts.insert("test".into(), 5).unwrap();
+        drop(db1);
 
         let db2 = Schema::init(db_path).unwrap();
         assert_eq!(db2.word_counts.get(&"test".into()).unwrap().unwrap(), 5);
         db2.word_counts.insert("test2".into(), 3).unwrap();
         assert_eq!(db2.word_counts.get(&"test2".into()).unwrap().unwrap(), 3);
+        drop(db2);
 
         let db3 = Schema::init(db_path).unwrap();
         assert_eq!(db3.word_counts.get(&"test2".into()).unwrap().unwrap(), 3);
@@ -112,10 +114,12 @@ pub mod schema {
 
         let db1 = Schema::init(db_path).unwrap();
         db1.word_counts.insert("test".into(), 234).unwrap();
+        drop(db1);
 
         let db2 = Schema::init(db_path).unwrap();
         db2.word_counts.delete("test".into()).unwrap();
 
+        drop(db2);
         let db2 = Schema::init(db_path).unwrap();
         db2.word_counts.delete("test".into()).unwrap();
 
@@ -145,6 +149,7 @@ pub mod schema {
             db1.word_counts.get(&"test".to_string()).unwrap().unwrap(),
             6
         );
+        drop(db1);
 
         let db2 = Schema::init(db_path).unwrap();
         assert_eq!(
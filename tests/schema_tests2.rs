@@ -1,7 +1,6 @@
 #[cfg(test)]
 pub mod tests {
-    use std::fs::File;
-    use std::path::PathBuf;
+    use std::path::{Path, PathBuf};
     use std::time::Duration;
     use std::{fs, thread};
 
@@ -11,7 +10,22 @@ pub mod tests {
     use crate::tests::schema::{Db, Test, Value};
     use hmdb::transaction::Transaction;
 
-    const SCHEMA_NAME: &str = "schema_tests2__tests__schema__Db";
+    const SCHEMA_NAME: &str = "Db";
+    /// What every file of a log holds before any records are written to it
+    const HEADER_LEN: u64 = 248;
+
+    /// The size of the records in the live segment and the snapshot, see `hmdb::log::LogFiles`.
+    fn log_len(db_path: &Path) -> u64 {
+        fs::read_dir(db_path)
+            .unwrap()
+            .map(|entry| entry.unwrap())
+            .filter(|entry| {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                name == SCHEMA_NAME || name.starts_with(&format!("{}.snapshot.", SCHEMA_NAME))
+            })
+            .map(|entry| entry.metadata().unwrap().len() - HEADER_LEN)
+            .sum()
+    }
 
     mod schema {
         use hmdb::schema;
@@ -58,6 +72,7 @@ pub mod tests {
         assert!(db.table1.exists(&Test {}).unwrap());
         assert!(!db.table2.exists(&Test {}).unwrap());
 
+        drop(db);
         let db = Db::init(db_path).unwrap();
         assert!(db.table1.exists(&Test {}).unwrap());
         assert!(!db.table2.exists(&Test {}).unwrap());
@@ -71,6 +86,7 @@ pub mod tests {
         assert!(!db.table1.exists(&Test {}).unwrap());
         assert_eq!(db.table2.get(&Test {}).unwrap().unwrap(), u128::MAX);
 
+        drop(db);
         let db = Db::init(db_path).unwrap();
         assert!(!db.table1.exists(&Test {}).unwrap());
         assert_eq!(db.table2.get(&Test {}).unwrap().unwrap(), u128::MAX);
@@ -85,6 +101,7 @@ pub mod tests {
         fs::remove_dir_all(db_path).unwrap_or_else(|_| println!("starting log did not exist"));
         let db = Db::init(db_path).unwrap();
         db.table3.insert("Test".to_string(), vec![1, 2, 3]).unwrap();
+        drop(db);
         let db = Db::init(db_path).unwrap();
         assert_eq!(
             db.table3.get(&"Test".to_string()).unwrap().unwrap(),
@@ -110,6 +127,7 @@ pub mod tests {
             )
             .unwrap();
 
+        drop(db);
         let db = Db::init(db_path).unwrap();
         assert_eq!(
             db.table4.get(&1).unwrap().unwrap(),
@@ -140,6 +158,7 @@ pub mod tests {
         })
         .unwrap();
 
+        drop(db);
         let db = Db::init(db_path).unwrap();
         assert_eq!(
             db.table4.get(&1).unwrap().unwrap(),
@@ -191,6 +210,7 @@ pub mod tests {
         db.table3.insert("c".to_string(), vec![1, 2, 3]).unwrap();
         db.table3.insert("d".to_string(), vec![1, 2, 3]).unwrap();
 
+        drop(db);
         let db = Db::init(db_path).unwrap();
         db.transaction(|tx| {
             tx.table3.clear();
@@ -214,12 +234,14 @@ pub mod tests {
         db.table3.insert("c".to_string(), vec![1, 2, 3]).unwrap();
         db.table3.insert("d".to_string(), vec![1, 2, 3]).unwrap();
 
+        drop(db);
         let db = Db::init(db_path).unwrap();
         db.transaction(|tx| {
             tx.table3.clear();
         })
         .unwrap();
 
+        drop(db);
         let db = Db::init(db_path).unwrap();
         assert!(db.table3.get_all().unwrap().is_empty());
         fs::remove_dir_all(db_path).unwrap_or(());
@@ -235,19 +257,12 @@ pub mod tests {
         db.table1.insert(Test {}, "apple".to_string()).unwrap();
         db.table1.insert(Test {}, "sauce".to_string()).unwrap();
 
-        let size_before = File::open(db_path.join(SCHEMA_NAME))
-            .unwrap()
-            .metadata()
-            .unwrap()
-            .len();
-        db.start_background_compacter(Duration::from_secs(3))
+        let size_before = log_len(db_path);
+        let _compacter = db
+            .start_background_compacter(Duration::from_secs(3))
             .unwrap();
         thread::sleep(Duration::from_secs(10));
-        let size_after = File::open(db_path.join(SCHEMA_NAME))
-            .unwrap()
-            .metadata()
-            .unwrap()
-            .len();
+        let size_after = log_len(db_path);
 
         assert!(size_before > size_after);
 
@@ -272,19 +287,12 @@ pub mod tests {
         db.table3.insert("c".to_string(), vec![1, 3]).unwrap();
         db.table3.insert("d".to_string(), vec![3, 2, 1]).unwrap();
 
-        let size_before = File::open(db_path.join(SCHEMA_NAME))
-            .unwrap()
-            .metadata()
-            .unwrap()
-            .len();
-        db.start_background_compacter(Duration::from_secs(3))
+        let size_before = log_len(db_path);
+        let _compacter = db
+            .start_background_compacter(Duration::from_secs(3))
             .unwrap();
         thread::sleep(Duration::from_secs(10));
-        let size_after = File::open(db_path.join(SCHEMA_NAME))
-            .unwrap()
-            .metadata()
-            .unwrap()
-            .len();
+        let size_after = log_len(db_path);
 
         assert!(size_before > size_after);
 
@@ -326,20 +334,13 @@ pub mod tests {
             )
             .unwrap();
 
-        let size_before = File::open(db_path.join(SCHEMA_NAME))
-            .unwrap()
-            .metadata()
-            .unwrap()
-            .len();
-        db.start_background_compacter(Duration::from_secs(3))
+        let size_before = log_len(db_path);
+        let _compacter = db
+            .start_background_compacter(Duration::from_secs(3))
             .unwrap();
         thread::sleep(Duration::from_secs(5));
 
-        let size_after = File::open(db_path.join(SCHEMA_NAME))
-            .unwrap()
-            .metadata()
-            .unwrap()
-            .len();
+        let size_after = log_len(db_path);
 
         assert!(size_before > size_after);
 
@@ -361,24 +362,17 @@ pub mod tests {
         fs::remove_dir_all(db_path).unwrap_or(());
         let db = Db::init(db_path).unwrap();
 
-        let size_before = File::open(db_path.join(SCHEMA_NAME))
-            .unwrap()
-            .metadata()
-            .unwrap()
-            .len();
+        let size_before = log_len(db_path);
 
-        db.start_background_compacter(Duration::from_secs(3))
+        let _compacter = db
+            .start_background_compacter(Duration::from_secs(3))
             .unwrap();
         thread::sleep(Duration::from_secs(5));
 
-        let size_after = File::open(db_path.join(SCHEMA_NAME))
-            .unwrap()
-            .metadata()
-            .unwrap()
-            .len();
+        let size_after = log_len(db_path);
 
         assert_eq!(size_before, 0);
-        assert_eq!(size_after, 16);
+        assert_eq!(size_after, 24);
 
         fs::remove_dir_all(db_path).unwrap_or(());
     }
@@ -395,21 +389,14 @@ pub mod tests {
         db.table3.insert("d".to_string(), vec![1]).unwrap();
         db.table3.insert("d".to_string(), vec![1, 3]).unwrap();
 
-        let size_before = File::open(db_path.join(SCHEMA_NAME))
-            .unwrap()
-            .metadata()
-            .unwrap()
-            .len();
+        let size_before = log_len(db_path);
 
         db.transaction(|tx| tx.table3.clear()).unwrap();
-        db.start_background_compacter(Duration::from_secs(3))
+        let _compacter = db
+            .start_background_compacter(Duration::from_secs(3))
             .unwrap();
         thread::sleep(Duration::from_secs(5));
-        let size_after = File::open(db_path.join(SCHEMA_NAME))
-            .unwrap()
-            .metadata()
-            .unwrap()
-            .len();
+        let size_after = log_len(db_path);
 
         assert!(size_before > size_after);
 
@@ -430,21 +417,14 @@ pub mod tests {
         db.table3.insert("b".to_string(), vec![1, 3]).unwrap();
         db.table3.insert("b".to_string(), vec![1, 2]).unwrap();
 
-        let size_before = File::open(db_path.join(SCHEMA_NAME))
-            .unwrap()
-            .metadata()
-            .unwrap()
-            .len();
+        let size_before = log_len(db_path);
 
-        db.start_background_compacter(Duration::from_secs(3))
+        let _compacter = db
+            .start_background_compacter(Duration::from_secs(3))
             .unwrap();
         thread::sleep(Duration::from_secs(5));
 
-        let size_after = File::open(db_path.join(SCHEMA_NAME))
-            .unwrap()
-            .metadata()
-            .unwrap()
-            .len();
+        let size_after = log_len(db_path);
 
         assert!(size_before > size_after);
 
@@ -460,19 +440,11 @@ pub mod tests {
         db.table2.insert(Test {}, 15).unwrap();
         db.table2.insert(Test {}, 20).unwrap();
 
-        let size_before = File::open(db_path.join(SCHEMA_NAME))
-            .unwrap()
-            .metadata()
-            .unwrap()
-            .len();
+        let size_before = log_len(db_path);
 
         thread::sleep(Duration::from_secs(4));
 
-        let size_after = File::open(db_path.join(SCHEMA_NAME))
-            .unwrap()
-            .metadata()
-            .unwrap()
-            .len();
+        let size_after = log_len(db_path);
 
         assert!(size_before > size_after);
 
@@ -493,20 +465,12 @@ pub mod tests {
         db.table1.insert(Test {}, "test".to_string()).unwrap();
         db.table1.insert(Test {}, "test".to_string()).unwrap();
 
-        let size_before = File::open(db_path.join(SCHEMA_NAME))
-            .unwrap()
-            .metadata()
-            .unwrap()
-            .len();
+        let size_before = log_len(db_path);
         db.compact_log().unwrap();
-        let size_after = File::open(db_path.join(SCHEMA_NAME))
-            .unwrap()
-            .metadata()
-            .unwrap()
-            .len();
+        let size_after = log_len(db_path);
 
-        assert_eq!(size_before, 112);
-        assert_eq!(size_after, 36);
+        assert_eq!(size_before, 176);
+        assert_eq!(size_after, 52);
 
         assert_eq!(
             db.table1.get(&Test {}).unwrap().unwrap(),
@@ -533,20 +497,12 @@ pub mod tests {
         db.table3.insert("b".to_string(), vec![1]).unwrap();
         db.table3.insert("b".to_string(), vec![1, 3]).unwrap();
 
-        let size_before = File::open(db_path.join(SCHEMA_NAME))
-            .unwrap()
-            .metadata()
-            .unwrap()
-            .len();
+        let size_before = log_len(db_path);
         db.compact_log().unwrap();
-        let size_after = File::open(db_path.join(SCHEMA_NAME))
-            .unwrap()
-            .metadata()
-            .unwrap()
-            .len();
+        let size_after = log_len(db_path);
 
-        assert_eq!(size_before, 269);
-        assert_eq!(size_after, 95);
+        assert_eq!(size_before, 397);
+        assert_eq!(size_after, 127);
 
         assert_eq!(
             db.table3.get(&"a".to_string()).unwrap().unwrap(),
@@ -586,20 +542,12 @@ pub mod tests {
             )
             .unwrap();
 
-        let size_before = File::open(db_path.join(SCHEMA_NAME))
-            .unwrap()
-            .metadata()
-            .unwrap()
-            .len();
+        let size_before = log_len(db_path);
         db.compact_log().unwrap();
-        let size_after = File::open(db_path.join(SCHEMA_NAME))
-            .unwrap()
-            .metadata()
-            .unwrap()
-            .len();
+        let size_after = log_len(db_path);
 
-        assert_eq!(size_before, 78);
-        assert_eq!(size_after, 47);
+        assert_eq!(size_before, 110);
+        assert_eq!(size_after, 63);
 
         assert_eq!(
             db.table4.get(&1).unwrap().unwrap(),
@@ -619,22 +567,14 @@ pub mod tests {
         fs::remove_dir_all(db_path).unwrap_or(());
         let db = Db::init(db_path).unwrap();
 
-        let size_before = File::open(db_path.join(SCHEMA_NAME))
-            .unwrap()
-            .metadata()
-            .unwrap()
-            .len();
+        let size_before = log_len(db_path);
 
         db.compact_log().unwrap();
 
-        let size_after = File::open(db_path.join(SCHEMA_NAME))
-            .unwrap()
-            .metadata()
-            .unwrap()
-            .len();
+        let size_after = log_len(db_path);
 
         assert_eq!(size_before, 0);
-        assert_eq!(size_after, 16);
+        assert_eq!(size_after, 24);
 
         fs::remove_dir_all(db_path).unwrap_or(());
     }
@@ -651,23 +591,15 @@ pub mod tests {
         db.table3.insert("b".to_string(), vec![1]).unwrap();
         db.table3.insert("b".to_string(), vec![1, 3]).unwrap();
 
-        let size_before = File::open(db_path.join(SCHEMA_NAME))
-            .unwrap()
-            .metadata()
-            .unwrap()
-            .len();
+        let size_before = log_len(db_path);
 
         db.transaction(|tx| tx.table3.clear()).unwrap();
         db.compact_log().unwrap();
 
-        let size_after = File::open(db_path.join(SCHEMA_NAME))
-            .unwrap()
-            .metadata()
-            .unwrap()
-            .len();
+        let size_after = log_len(db_path);
 
-        assert_eq!(size_before, 141);
-        assert_eq!(size_after, 16);
+        assert_eq!(size_before, 205);
+        assert_eq!(size_after, 24);
 
         assert_eq!(db.table3.get(&"a".to_string()).unwrap(), None);
         assert_eq!(db.table3.get(&"b".to_string()).unwrap(), None);
@@ -0,0 +1,114 @@
+#[cfg(test)]
+pub mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use hmdb::log::{LogCompacter, Reader};
+    use uuid::Uuid;
+
+    use crate::tests::db::Db;
+
+    mod db {
+        use hmdb::schema;
+
+        schema! {
+            Db {
+                names: <u64, String>
+            }
+        }
+    }
+
+    const SEGMENT_BYTES: u64 = 512;
+
+    fn test_db() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("target")
+            .join(Uuid::new_v4().to_string())
+    }
+
+    fn open(db_path: &Path) -> Db {
+        Db::options()
+            .segment_bytes(SEGMENT_BYTES)
+            .open(db_path)
+            .unwrap()
+    }
+
+    fn manifest(db_path: &Path) -> Vec<String> {
+        fs::read_to_string(db_path.join("Db.manifest"))
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn full_segments_are_rolled_over() {
+        let db_path = &test_db();
+        let db = open(db_path);
+        for key in 0..100 {
+            db.names.insert(key, "value".repeat(5)).unwrap();
+        }
+
+        let listed = manifest(db_path);
+        assert!(listed.len() > 3, "{:?}", listed);
+        assert_eq!(listed[0], "Db.wal.1");
+        assert_eq!(listed.last().unwrap(), "Db");
+        for (segment, name) in listed[..listed.len() - 1].iter().enumerate() {
+            assert_eq!(*name, format!("Db.wal.{}", segment + 1));
+            let len = fs::metadata(db_path.join(name)).unwrap().len();
+            assert!((SEGMENT_BYTES..SEGMENT_BYTES * 2).contains(&len));
+        }
+        drop(db);
+
+        let db = open(db_path);
+        assert!(db.recovery_report().is_clean());
+        assert_eq!(db.names.get_all().unwrap().len(), 100);
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn manifest_follows_compactions() {
+        let db_path = &test_db();
+        let db = open(db_path);
+        for key in 0..50 {
+            db.names.insert(key, "value".repeat(5)).unwrap();
+        }
+        db.compact_log().unwrap();
+
+        let listed = manifest(db_path);
+        assert!(listed[0].starts_with("Db.snapshot."));
+        assert_eq!(listed[1..], ["Db"]);
+        for name in &listed {
+            assert!(db_path.join(name).exists());
+        }
+
+        db.names.insert(50, "value".repeat(200)).unwrap();
+        let rolled = manifest(db_path);
+        assert_eq!(rolled.len(), 3);
+        assert_eq!(rolled[0], listed[0]);
+        drop(db);
+
+        let db = open(db_path);
+        assert_eq!(db.names.get_all().unwrap().len(), 51);
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn readers_follow_rolled_segments() {
+        let db_path = &test_db();
+        let db = open(db_path);
+        db.names.insert(0, "zero".to_string()).unwrap();
+        let reader = Db::init_read_only(db_path).unwrap();
+
+        for key in 1..100 {
+            db.names.insert(key, "value".repeat(5)).unwrap();
+        }
+        reader.refresh().unwrap();
+        assert_eq!(reader.names.get_all().unwrap().len(), 100);
+        assert_eq!(reader.names.get(&0).unwrap().unwrap(), "zero");
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+}
@@ -0,0 +1,194 @@
+#[cfg(test)]
+pub mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::process::{Command, Stdio};
+    use std::thread;
+    use std::time::Duration;
+
+    use hmdb::errors::Error;
+    use hmdb::lock::Locking;
+    use hmdb::log::{LogCompacter, Reader};
+    use hmdb::options::Options;
+    use hmdb::transaction::Transaction;
+    use uuid::Uuid;
+
+    use crate::tests::schema::Db;
+
+    const SHARED_DB: &str = "HMDB_SHARED_DB";
+
+    mod schema {
+        use hmdb::schema;
+
+        schema! {
+            Db {
+                table1: <u64, String>,
+                table2: <String, u64>
+            }
+        }
+    }
+
+    fn test_db() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("target")
+            .join(Uuid::new_v4().to_string())
+    }
+
+    fn open_shared<P: AsRef<Path>>(db_path: P) -> Result<Db, Error> {
+        Db::init_with(
+            db_path,
+            Options {
+                locking: Locking::Shared,
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn writes_pick_up_other_handles() {
+        let db_path = &test_db();
+
+        let db1 = open_shared(db_path).unwrap();
+        let db2 = open_shared(db_path).unwrap();
+
+        db1.table1.insert(1, "one".to_string()).unwrap();
+        assert_eq!(db2.table1.get(&1).unwrap(), None);
+        db2.refresh().unwrap();
+        assert_eq!(db2.table1.get(&1).unwrap().unwrap(), "one");
+
+        db2.table1.insert(2, "two".to_string()).unwrap();
+        db1.table1.insert(3, "three".to_string()).unwrap();
+        assert_eq!(db1.table1.get(&2).unwrap().unwrap(), "two");
+
+        drop((db1, db2));
+        let db = Db::init(db_path).unwrap();
+        assert_eq!(db.table1.get_all().unwrap().len(), 3);
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn transactions_see_other_handles_writes() {
+        let db_path = &test_db();
+
+        let db1 = open_shared(db_path).unwrap();
+        let db2 = open_shared(db_path).unwrap();
+
+        let increment = |db: &Db| {
+            db.transaction(|tx| {
+                let count = tx.table2.get(&"count".to_string()).cloned().unwrap_or(0);
+                tx.table2.insert("count".to_string(), count + 1);
+            })
+            .unwrap()
+        };
+        for _ in 0..5 {
+            increment(&db1);
+            increment(&db2);
+        }
+
+        assert_eq!(db2.table2.get(&"count".to_string()).unwrap().unwrap(), 10);
+        db1.refresh().unwrap();
+        assert_eq!(db1.table2.get(&"count".to_string()).unwrap().unwrap(), 10);
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn compaction_is_picked_up() {
+        let db_path = &test_db();
+
+        let db1 = open_shared(db_path).unwrap();
+        let db2 = open_shared(db_path).unwrap();
+
+        db1.table1.insert(1, "one".to_string()).unwrap();
+        db2.table1.insert(2, "two".to_string()).unwrap();
+        db1.table1.delete(2).unwrap();
+        db1.compact_log().unwrap();
+
+        // db2 still has the old log open, it has to notice the new one before appending
+        db2.table1.insert(3, "three".to_string()).unwrap();
+        assert_eq!(db2.table1.get(&2).unwrap(), None);
+        db1.refresh().unwrap();
+        assert_eq!(db1.table1.get(&3).unwrap().unwrap(), "three");
+
+        drop((db1, db2));
+        let db = Db::init(db_path).unwrap();
+        let table1 = db.table1.get_all().unwrap();
+        assert_eq!(table1.len(), 2);
+        assert!(table1.contains_key(&1) && table1.contains_key(&3));
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn locking_modes_cant_be_mixed() {
+        let db_path = &test_db();
+
+        let db = open_shared(db_path).unwrap();
+        assert!(matches!(Db::init(db_path), Err(Error::AlreadyOpen(_, _))));
+        drop(db);
+
+        let db = Db::init(db_path).unwrap();
+        assert!(matches!(
+            open_shared(db_path),
+            Err(Error::AlreadyOpen(_, _))
+        ));
+        drop(db);
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    /// Run by `other_processes_writes_are_picked_up` in a child process.
+    #[test]
+    #[ignore]
+    fn shared_child() {
+        let db_path = match env::var(SHARED_DB) {
+            Ok(db_path) => db_path,
+            Err(_) => return,
+        };
+
+        let db = open_shared(db_path).unwrap();
+        db.table1.insert(100, "child".to_string()).unwrap();
+        loop {
+            thread::sleep(Duration::from_secs(1));
+        }
+    }
+
+    #[test]
+    fn other_processes_writes_are_picked_up() {
+        let db_path = &test_db();
+
+        let db = open_shared(db_path).unwrap();
+        db.table1.insert(1, "parent".to_string()).unwrap();
+
+        let mut child = Command::new(env::current_exe().unwrap())
+            .args(["tests::shared_child", "--exact", "--ignored"])
+            .env(SHARED_DB, db_path)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+
+        for _ in 0..500 {
+            db.refresh().unwrap();
+            if db.table1.exists(&100).unwrap() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(db.table1.get(&100).unwrap().unwrap(), "child");
+
+        // The child still has it open, so this process can't take it exclusively
+        drop(db);
+        assert!(matches!(Db::init(db_path), Err(Error::AlreadyOpen(_, _))));
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+
+        let db = Db::init(db_path).unwrap();
+        assert_eq!(db.table1.get_all().unwrap().len(), 2);
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+}
@@ -0,0 +1,184 @@
+#[cfg(test)]
+pub mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::thread;
+
+    use hmdb::errors::Error;
+    use hmdb::log::{LogCompacter, Reader};
+    use uuid::Uuid;
+
+    use crate::tests::db::Db;
+
+    mod db {
+        use hmdb::schema;
+
+        schema! {
+            Db {
+                names: <u64, String>
+            }
+        }
+    }
+
+    const SCHEMA_NAME: &str = "Db";
+
+    fn test_db() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("target")
+            .join(Uuid::new_v4().to_string())
+    }
+
+    fn log_files(db_path: &Path) -> Vec<String> {
+        let mut names: Vec<_> = fs::read_dir(db_path)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name.starts_with(SCHEMA_NAME) && !name.ends_with("lock"))
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn compaction_replaces_segments_with_a_snapshot() {
+        let db_path = &test_db();
+        let db = Db::init(db_path).unwrap();
+        db.names.insert(1, "parth".to_string()).unwrap();
+        db.compact_log().unwrap();
+        assert_eq!(
+            log_files(db_path),
+            vec!["Db", "Db.manifest", "Db.snapshot.1"]
+        );
+
+        db.names.insert(2, "travis".to_string()).unwrap();
+        db.compact_log().unwrap();
+        db.names.insert(3, "smail".to_string()).unwrap();
+        assert_eq!(
+            log_files(db_path),
+            vec!["Db", "Db.manifest", "Db.snapshot.2"]
+        );
+        drop(db);
+
+        let db = Db::init(db_path).unwrap();
+        assert_eq!(db.names.get_all().unwrap().len(), 3);
+        assert_eq!(db.names.get(&3).unwrap().unwrap(), "smail");
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn writes_made_during_compactions_are_kept() {
+        let db_path = &test_db();
+        let db = Db::init(db_path).unwrap();
+
+        let compacter = db.clone();
+        let compactions = thread::spawn(move || {
+            for _ in 0..20 {
+                compacter.compact_log().unwrap();
+            }
+        });
+        for key in 0..200 {
+            db.names.insert(key, key.to_string()).unwrap();
+        }
+        compactions.join().unwrap();
+        drop(db);
+
+        let db = Db::init(db_path).unwrap();
+        assert_eq!(db.names.get_all().unwrap().len(), 200);
+        assert_eq!(db.names.get(&199).unwrap().unwrap(), "199");
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn snapshot_builds_on_the_previous_one() {
+        let db_path = &test_db();
+        let db = Db::init(db_path).unwrap();
+        db.names.insert(1, "parth".to_string()).unwrap();
+        db.names.insert(2, "travis".to_string()).unwrap();
+        db.compact_log().unwrap();
+
+        // Replayed from the first snapshot and the segment sealed after it
+        db.names.delete(1).unwrap();
+        db.names.insert(3, "smail".to_string()).unwrap();
+        db.compact_log().unwrap();
+        let names = db.names.get_all().unwrap();
+        drop(db);
+        assert_eq!(
+            log_files(db_path),
+            vec!["Db", "Db.manifest", "Db.snapshot.2"]
+        );
+
+        let db = Db::init(db_path).unwrap();
+        assert_eq!(db.names.get_all().unwrap(), names);
+        assert_eq!(db.names.get(&1).unwrap(), None);
+        assert_eq!(db.names.get(&3).unwrap().unwrap(), "smail");
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn files_left_by_an_interrupted_compaction_are_recovered() {
+        let db_path = &test_db();
+        let db = Db::init(db_path).unwrap();
+        db.names.insert(1, "parth".to_string()).unwrap();
+        db.compact_log().unwrap();
+        db.names.insert(2, "travis".to_string()).unwrap();
+        drop(db);
+
+        // Sealed, but its snapshot was never written
+        let live = db_path.join(SCHEMA_NAME);
+        fs::rename(&live, db_path.join("Db.wal.2")).unwrap();
+        let db = Db::init(db_path).unwrap();
+        assert_eq!(db.names.get_all().unwrap().len(), 2);
+        db.names.insert(3, "smail".to_string()).unwrap();
+        drop(db);
+
+        // Covered by a snapshot, but never replaced
+        let obsolete = fs::read(&live).unwrap();
+        let db = Db::init(db_path).unwrap();
+        db.compact_log().unwrap();
+        drop(db);
+        fs::write(&live, obsolete).unwrap();
+        let db = Db::init(db_path).unwrap();
+        assert_eq!(db.names.get_all().unwrap().len(), 3);
+        assert_eq!(
+            log_files(db_path),
+            vec!["Db", "Db.manifest", "Db.snapshot.3"]
+        );
+        drop(db);
+
+        // A segment went missing
+        fs::write(db_path.join("Db.wal.5"), b"").unwrap();
+        match Db::init(db_path) {
+            Err(Error::CorruptLog(msg)) => assert!(msg.contains("Db.wal.4"), "{}", msg),
+            other => panic!("expected Error::CorruptLog, got {:?}", other.map(|_| ())),
+        }
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn readers_follow_compactions() {
+        let db_path = &test_db();
+        let db = Db::init(db_path).unwrap();
+        db.names.insert(1, "parth".to_string()).unwrap();
+        let reader = Db::init_read_only(db_path).unwrap();
+
+        db.names.insert(2, "travis".to_string()).unwrap();
+        db.compact_log().unwrap();
+        db.names.insert(3, "smail".to_string()).unwrap();
+        reader.refresh().unwrap();
+        assert_eq!(reader.names.get_all().unwrap().len(), 3);
+
+        // The segments in between are gone, so the reader starts over from the snapshot
+        db.names.delete(1).unwrap();
+        db.compact_log().unwrap();
+        db.compact_log().unwrap();
+        db.names.insert(4, "adam".to_string()).unwrap();
+        reader.refresh().unwrap();
+        assert_eq!(reader.names.get(&1).unwrap(), None);
+        assert_eq!(reader.names.get_all().unwrap().len(), 3);
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+}
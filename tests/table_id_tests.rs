@@ -0,0 +1,127 @@
+#[cfg(test)]
+pub mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use hmdb::errors::Error;
+    use hmdb::log::{LogCompacter, Reader};
+    use uuid::Uuid;
+
+    use crate::tests::schema::Db;
+
+    mod schema {
+        use hmdb::schema;
+
+        schema! {
+            Db {
+                #[id = 1]
+                files: <u64, String>,
+                names: <String, u64>
+            }
+        }
+    }
+
+    mod reordered {
+        use hmdb::schema;
+
+        schema! {
+            Reordered as "Db" {
+                sizes: <u64, u64>,
+                names: <String, u64>,
+                #[id = 1]
+                files: <u64, String>
+            }
+        }
+    }
+
+    mod removed {
+        use hmdb::schema;
+
+        schema! {
+            Removed as "Db" {
+                #[id = 1]
+                documents: <u64, String>
+            }
+        }
+    }
+
+    mod renumbered {
+        use hmdb::schema;
+
+        schema! {
+            Renumbered as "Db" {
+                #[id = 2]
+                files: <u64, String>
+            }
+        }
+    }
+
+    fn test_db() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("target")
+            .join(Uuid::new_v4().to_string())
+    }
+
+    fn populate(db_path: &PathBuf) {
+        let db = Db::init(db_path).unwrap();
+        db.files.insert(1, "one".to_string()).unwrap();
+        db.names.insert("one".to_string(), 1).unwrap();
+        drop(db);
+    }
+
+    #[test]
+    fn tables_can_be_reordered_and_added() {
+        let db_path = &test_db();
+        populate(db_path);
+
+        let db = reordered::Reordered::init(db_path).unwrap();
+        assert_eq!(db.files.get(&1).unwrap().unwrap(), "one");
+        assert_eq!(db.names.get(&"one".to_string()).unwrap().unwrap(), 1);
+        assert!(db.sizes.get_all().unwrap().is_empty());
+        db.sizes.insert(1, 3).unwrap();
+        drop(db);
+
+        // The table the original schema doesn't know about is ignored
+        let db = Db::init(db_path).unwrap();
+        assert_eq!(db.files.get(&1).unwrap().unwrap(), "one");
+        assert_eq!(db.names.get(&"one".to_string()).unwrap().unwrap(), 1);
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn tables_with_ids_can_be_renamed_and_others_removed() {
+        let db_path = &test_db();
+        populate(db_path);
+
+        let db = removed::Removed::init(db_path).unwrap();
+        assert_eq!(db.documents.get(&1).unwrap().unwrap(), "one");
+        db.compact_log().unwrap();
+        drop(db);
+
+        // Compaction dropped the records of the removed table
+        let db = Db::init(db_path).unwrap();
+        assert_eq!(db.files.get(&1).unwrap().unwrap(), "one");
+        assert!(db.names.get_all().unwrap().is_empty());
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn changed_id_is_rejected() {
+        let db_path = &test_db();
+        populate(db_path);
+
+        match renumbered::Renumbered::init(db_path) {
+            Err(Error::SchemaMismatch(msg)) => assert!(msg.contains("#[id = 1]"), "{}", msg),
+            other => panic!(
+                "expected Error::SchemaMismatch, got {:?}",
+                other.map(|_| ())
+            ),
+        }
+        let db = Db::init(db_path).unwrap();
+        assert_eq!(db.files.get(&1).unwrap().unwrap(), "one");
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+}
@@ -0,0 +1,199 @@
+#[cfg(test)]
+pub mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use hmdb::errors::Error;
+    use hmdb::log::Reader;
+    use serde::{Deserialize, Serialize};
+    use uuid::Uuid;
+
+    use crate::tests::v1::Db;
+    use crate::tests::v2::DbV2;
+    use crate::tests::v3::DbV3;
+
+    const SCHEMA_NAME: &str = "Db";
+
+    #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct AccountV1 {
+        name: String,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct AccountV2 {
+        name: String,
+        email: Option<String>,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct AccountV3 {
+        name: String,
+        email: Option<String>,
+        admin: bool,
+    }
+
+    impl From<AccountV1> for AccountV2 {
+        fn from(account: AccountV1) -> Self {
+            AccountV2 {
+                name: account.name,
+                email: None,
+            }
+        }
+    }
+
+    impl From<AccountV2> for AccountV3 {
+        fn from(account: AccountV2) -> Self {
+            AccountV3 {
+                name: account.name,
+                email: account.email,
+                admin: false,
+            }
+        }
+    }
+
+    impl From<AccountV1> for AccountV3 {
+        fn from(account: AccountV1) -> Self {
+            AccountV2::from(account).into()
+        }
+    }
+
+    mod v1 {
+        use super::AccountV1;
+        use hmdb::schema;
+
+        schema! {
+            Db {
+                accounts: <u64, AccountV1>,
+                names: <u64, String>
+            }
+        }
+    }
+
+    mod v2 {
+        use super::{AccountV1, AccountV2};
+        use hmdb::schema;
+
+        schema! {
+            DbV2 as "Db" {
+                accounts: <u64, AccountV2> from [AccountV1],
+                names: <u64, String>
+            }
+        }
+    }
+
+    mod v3 {
+        use super::{AccountV1, AccountV2, AccountV3};
+        use hmdb::schema;
+
+        schema! {
+            DbV3 as "Db" {
+                accounts: <u64, AccountV3> from [AccountV1, AccountV2],
+                names: <u64, String>
+            }
+        }
+    }
+
+    mod skipped {
+        use super::{AccountV2, AccountV3};
+        use hmdb::schema;
+
+        schema! {
+            Skipped as "Db" {
+                accounts: <u64, AccountV3> from [AccountV2],
+                names: <u64, String>
+            }
+        }
+    }
+
+    fn test_db() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("target")
+            .join(Uuid::new_v4().to_string())
+    }
+
+    fn populate(db_path: &PathBuf) {
+        let db = Db::init(db_path).unwrap();
+        db.accounts
+            .insert(
+                1,
+                AccountV1 {
+                    name: "parth".to_string(),
+                },
+            )
+            .unwrap();
+        db.names.insert(1, "parth".to_string()).unwrap();
+    }
+
+    fn parth(email: Option<&str>) -> AccountV3 {
+        AccountV3 {
+            name: "parth".to_string(),
+            email: email.map(str::to_string),
+            admin: false,
+        }
+    }
+
+    #[test]
+    fn earlier_values_are_upcast_and_rewritten() {
+        let db_path = &test_db();
+        populate(db_path);
+
+        let db = DbV2::init(db_path).unwrap();
+        assert_eq!(db.recovery_report().upcast_tables, ["accounts"]);
+        db.accounts
+            .insert(
+                2,
+                AccountV2 {
+                    name: "parth".to_string(),
+                    email: Some("parth@example.com".to_string()),
+                },
+            )
+            .unwrap();
+        drop(db);
+
+        let db = DbV3::init(db_path).unwrap();
+        assert_eq!(db.recovery_report().upcast_tables, ["accounts"]);
+        assert_eq!(db.accounts.get(&1).unwrap().unwrap(), parth(None));
+        assert_eq!(
+            db.accounts.get(&2).unwrap().unwrap(),
+            parth(Some("parth@example.com"))
+        );
+        assert_eq!(db.names.get(&1).unwrap().unwrap(), "parth");
+        drop(db);
+
+        // Opening for writing rewrote the log in the current type
+        let db = DbV3::init(db_path).unwrap();
+        assert!(db.recovery_report().upcast_tables.is_empty());
+        assert_eq!(db.accounts.get(&1).unwrap().unwrap(), parth(None));
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn read_only_upcasts_without_rewriting() {
+        let db_path = &test_db();
+        populate(db_path);
+        let log = fs::read(db_path.join(SCHEMA_NAME)).unwrap();
+
+        let db = DbV3::init_read_only(db_path).unwrap();
+        assert_eq!(db.accounts.get(&1).unwrap().unwrap(), parth(None));
+        assert_eq!(fs::read(db_path.join(SCHEMA_NAME)).unwrap(), log);
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+
+    #[test]
+    fn unlisted_value_type_is_rejected() {
+        let db_path = &test_db();
+        populate(db_path);
+
+        match skipped::Skipped::init(db_path) {
+            Err(Error::SchemaMismatch(msg)) => assert!(msg.contains("AccountV1"), "{}", msg),
+            other => panic!(
+                "expected Error::SchemaMismatch, got {:?}",
+                other.map(|_| ())
+            ),
+        }
+
+        fs::remove_dir_all(db_path).unwrap_or(());
+    }
+}